@@ -2,10 +2,13 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 // Copyright Tock Contributors 2024.
 
-//! Component for creating a process checking machine.
+//! Components for creating a process checking machine and an optional timeout
+//! for credential checks that never finish.
 
+use capsules_system::process_checker::timeout::AlarmCheckTimeout;
 use core::mem::MaybeUninit;
 use kernel::component::Component;
+use kernel::hil::time::Alarm;
 
 #[macro_export]
 macro_rules! process_checker_machine_component_static {
@@ -38,3 +41,49 @@ impl Component for ProcessCheckerMachineComponent {
         checker
     }
 }
+
+#[macro_export]
+macro_rules! process_checker_machine_timeout_component_static {
+    ($A:ty $(,)?) => {{
+        kernel::static_buf!(
+            capsules_system::process_checker::timeout::AlarmCheckTimeout<'static, $A>
+        )
+    };};
+}
+
+pub type ProcessCheckerMachineTimeoutComponentType<A> = AlarmCheckTimeout<'static, A>;
+
+/// Gives up on credential checks that do not finish within `timeout_ms`.
+pub struct ProcessCheckerMachineTimeoutComponent<A: Alarm<'static> + 'static> {
+    checker: &'static kernel::process::ProcessCheckerMachine,
+    alarm: &'static A,
+    timeout_ms: u32,
+}
+
+impl<A: Alarm<'static> + 'static> ProcessCheckerMachineTimeoutComponent<A> {
+    pub fn new(
+        checker: &'static kernel::process::ProcessCheckerMachine,
+        alarm: &'static A,
+        timeout_ms: u32,
+    ) -> Self {
+        Self {
+            checker,
+            alarm,
+            timeout_ms,
+        }
+    }
+}
+
+impl<A: Alarm<'static> + 'static> Component for ProcessCheckerMachineTimeoutComponent<A> {
+    type StaticInput = &'static mut MaybeUninit<AlarmCheckTimeout<'static, A>>;
+
+    type Output = &'static AlarmCheckTimeout<'static, A>;
+
+    fn finalize(self, s: Self::StaticInput) -> Self::Output {
+        let timeout = s.write(AlarmCheckTimeout::new(self.alarm, self.timeout_ms));
+
+        self.alarm.set_alarm_client(timeout);
+        self.checker.set_timeout(timeout);
+        timeout
+    }
+}
@@ -0,0 +1,78 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright Tock Contributors 2024.
+
+//! Component for a credential checker that accepts or rejects processes based
+//! on the type of their credentials.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! use tock_tbf::types::TbfFooterV2CredentialsType;
+//!
+//! let checker = components::appid::checker_allowlist::AppCheckerTypeAllowlistComponent::new(
+//!     &[TbfFooterV2CredentialsType::SHA256],
+//!     &[TbfFooterV2CredentialsType::Reserved],
+//!     true,
+//! )
+//! .finalize(components::app_checker_type_allowlist_component_static!());
+//! ```
+
+use core::mem::MaybeUninit;
+use kernel::component::Component;
+use kernel::deferred_call::DeferredCallClient;
+use tock_tbf::types::TbfFooterV2CredentialsType;
+
+#[macro_export]
+macro_rules! app_checker_type_allowlist_component_static {
+    () => {{
+        kernel::static_buf!(
+            capsules_system::process_checker::allowlist::AppCheckerTypeAllowlist<'static>
+        )
+    };};
+}
+
+pub type AppCheckerTypeAllowlistComponentType =
+    capsules_system::process_checker::allowlist::AppCheckerTypeAllowlist<'static>;
+
+pub struct AppCheckerTypeAllowlistComponent {
+    allowed: &'static [TbfFooterV2CredentialsType],
+    disallowed: &'static [TbfFooterV2CredentialsType],
+    require_credentials: bool,
+}
+
+impl AppCheckerTypeAllowlistComponent {
+    pub fn new(
+        allowed: &'static [TbfFooterV2CredentialsType],
+        disallowed: &'static [TbfFooterV2CredentialsType],
+        require_credentials: bool,
+    ) -> Self {
+        Self {
+            allowed,
+            disallowed,
+            require_credentials,
+        }
+    }
+}
+
+impl Component for AppCheckerTypeAllowlistComponent {
+    type StaticInput = &'static mut MaybeUninit<
+        capsules_system::process_checker::allowlist::AppCheckerTypeAllowlist<'static>,
+    >;
+    type Output =
+        &'static capsules_system::process_checker::allowlist::AppCheckerTypeAllowlist<'static>;
+
+    fn finalize(self, s: Self::StaticInput) -> Self::Output {
+        let checker = s.write(
+            capsules_system::process_checker::allowlist::AppCheckerTypeAllowlist::new(
+                self.allowed,
+                self.disallowed,
+                self.require_credentials,
+            ),
+        );
+        checker.register();
+
+        checker
+    }
+}
@@ -5,5 +5,7 @@
 pub mod assigner_name;
 pub mod assigner_tbf;
 pub mod checker;
+pub mod checker_allowlist;
 pub mod checker_null;
 pub mod checker_sha;
+pub mod checker_signature;
@@ -153,3 +153,64 @@ impl<
         hmac_sha256_sw
     }
 }
+
+#[macro_export]
+macro_rules! hmac_sha512_software_component_static {
+    ($S:ty $(,)?) => {{
+        let hmac_sha512 =
+            kernel::static_buf!(capsules_extra::hmac_sha512::HmacSha512Software<'static, $S>);
+
+        let data_buffer = kernel::static_buf!([u8; 128]);
+        let verify_buffer = kernel::static_buf!([u8; 64]);
+
+        (hmac_sha512, data_buffer, verify_buffer)
+    };};
+}
+
+pub type HmacSha512SoftwareComponentType<S> =
+    capsules_extra::hmac_sha512::HmacSha512Software<'static, S>;
+
+pub struct HmacSha512SoftwareComponent<
+    S: digest::Sha512 + digest::DigestDataHash<'static, 64> + digest::Digest<'static, 64> + 'static,
+> {
+    sha_512: &'static S,
+}
+
+impl<S: digest::Sha512 + digest::DigestDataHash<'static, 64> + digest::Digest<'static, 64>>
+    HmacSha512SoftwareComponent<S>
+{
+    pub fn new(sha_512: &'static S) -> HmacSha512SoftwareComponent<S> {
+        HmacSha512SoftwareComponent { sha_512 }
+    }
+}
+
+impl<
+        S: digest::Sha512
+            + digest::DigestDataHash<'static, 64>
+            + digest::Digest<'static, 64>
+            + 'static,
+    > Component for HmacSha512SoftwareComponent<S>
+{
+    type StaticInput = (
+        &'static mut MaybeUninit<capsules_extra::hmac_sha512::HmacSha512Software<'static, S>>,
+        &'static mut MaybeUninit<[u8; 128]>,
+        &'static mut MaybeUninit<[u8; 64]>,
+    );
+    type Output = &'static capsules_extra::hmac_sha512::HmacSha512Software<'static, S>;
+
+    fn finalize(self, s: Self::StaticInput) -> Self::Output {
+        let data_buffer = s.1.write([0; 128]);
+        let verify_buffer = s.2.write([0; 64]);
+
+        let hmac_sha512_sw =
+            s.0.write(capsules_extra::hmac_sha512::HmacSha512Software::new(
+                self.sha_512,
+                data_buffer,
+                verify_buffer,
+            ));
+
+        kernel::hil::digest::Digest::set_client(self.sha_512, hmac_sha512_sw);
+
+        hmac_sha512_sw
+    }
+}
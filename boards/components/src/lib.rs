@@ -72,6 +72,7 @@ pub mod rf233;
 pub mod rng;
 pub mod sched;
 pub mod screen;
+pub mod screen_double_buffer;
 pub mod segger_rtt;
 pub mod sh1106;
 pub mod sha;
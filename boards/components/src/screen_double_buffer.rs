@@ -0,0 +1,80 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright Tock Contributors 2024.
+
+//! Component for double buffering a screen.
+//!
+//! Both framebuffers must hold the entire screen, so the buffer size is
+//! `width * height * bytes_per_pixel` of the underlying screen.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let double_buffer =
+//!     components::screen_double_buffer::ScreenDoubleBufferComponent::new(ssd1306)
+//!         .finalize(components::screen_double_buffer_component_static!(
+//!             components::ssd1306::Ssd1306ComponentType<nrf52840::i2c::TWI>,
+//!             1024
+//!         ));
+//! ```
+
+use capsules_extra::screen_adapters::ScreenDoubleBuffer;
+use core::mem::MaybeUninit;
+use kernel::component::Component;
+use kernel::deferred_call::DeferredCallClient;
+use kernel::hil;
+
+// Setup static space for the objects.
+#[macro_export]
+macro_rules! screen_double_buffer_component_static {
+    ($S:ty, $s:expr $(,)?) => {{
+        let back = kernel::static_buf!([u8; $s]);
+        let front = kernel::static_buf!([u8; $s]);
+        let double_buffer =
+            kernel::static_buf!(capsules_extra::screen_adapters::ScreenDoubleBuffer<'static, $S>);
+
+        (back, front, double_buffer)
+    };};
+}
+
+pub type ScreenDoubleBufferComponentType<S> = ScreenDoubleBuffer<'static, S>;
+
+pub struct ScreenDoubleBufferComponent<
+    S: hil::screen::Screen<'static> + 'static,
+    const BUFFER_SIZE: usize,
+> {
+    screen: &'static S,
+}
+
+impl<S: hil::screen::Screen<'static> + 'static, const BUFFER_SIZE: usize>
+    ScreenDoubleBufferComponent<S, BUFFER_SIZE>
+{
+    pub fn new(screen: &'static S) -> Self {
+        Self { screen }
+    }
+}
+
+impl<S: hil::screen::Screen<'static> + 'static, const BUFFER_SIZE: usize> Component
+    for ScreenDoubleBufferComponent<S, BUFFER_SIZE>
+{
+    type StaticInput = (
+        &'static mut MaybeUninit<[u8; BUFFER_SIZE]>,
+        &'static mut MaybeUninit<[u8; BUFFER_SIZE]>,
+        &'static mut MaybeUninit<ScreenDoubleBuffer<'static, S>>,
+    );
+    type Output = &'static ScreenDoubleBuffer<'static, S>;
+
+    fn finalize(self, static_buffer: Self::StaticInput) -> Self::Output {
+        let back = static_buffer.0.write([0; BUFFER_SIZE]);
+        let front = static_buffer.1.write([0; BUFFER_SIZE]);
+
+        let double_buffer =
+            static_buffer
+                .2
+                .write(ScreenDoubleBuffer::new(self.screen, back, front));
+        double_buffer.register();
+        self.screen.set_client(double_buffer);
+
+        double_buffer
+    }
+}
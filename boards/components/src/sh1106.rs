@@ -11,8 +11,12 @@
 //! let oled_i2c = components::i2c::I2CComponent::new(i2c_bus, 0x3c)
 //!     .finalize(components::i2c_component_static!(nrf52840::i2c::TWI));
 //!
-//! let sh1106 = components::sh1106::Sh1106Component::new(oled_i2c, true)
-//!     .finalize(components::sh1106_component_static!(nrf52840::i2c::TWI));
+//! let sh1106 = components::sh1106::Sh1106Component::new(
+//!     oled_i2c,
+//!     true,
+//!     capsules_extra::sh1106::DEFAULT_COLUMN_OFFSET,
+//! )
+//! .finalize(components::sh1106_component_static!(nrf52840::i2c::TWI));
 //! ```
 
 use core::mem::MaybeUninit;
@@ -43,16 +47,19 @@ pub type Sh1106ComponentType<I> = capsules_extra::sh1106::Sh1106<
 pub struct Sh1106Component<I: hil::i2c::I2CMaster<'static> + 'static> {
     i2c_device: &'static capsules_core::virtualizers::virtual_i2c::I2CDevice<'static, I>,
     use_charge_pump: bool,
+    column_offset: u8,
 }
 
 impl<I: hil::i2c::I2CMaster<'static> + 'static> Sh1106Component<I> {
     pub fn new(
         i2c_device: &'static capsules_core::virtualizers::virtual_i2c::I2CDevice<'static, I>,
         use_charge_pump: bool,
+        column_offset: u8,
     ) -> Sh1106Component<I> {
         Sh1106Component {
             i2c_device,
             use_charge_pump,
+            column_offset,
         }
     }
 }
@@ -81,6 +88,7 @@ impl<I: hil::i2c::I2CMaster<'static> + 'static> Component for Sh1106Component<I>
             self.i2c_device,
             buffer,
             self.use_charge_pump,
+            self.column_offset,
         ));
         self.i2c_device.set_client(sh1106);
 
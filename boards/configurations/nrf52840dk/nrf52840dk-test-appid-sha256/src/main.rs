@@ -10,6 +10,7 @@
 #![cfg_attr(not(doc), no_main)]
 #![deny(missing_docs)]
 
+use capsules_core::virtualizers::virtual_alarm::VirtualMuxAlarm;
 use core::ptr::{addr_of, addr_of_mut};
 
 use kernel::component::Component;
@@ -271,6 +272,24 @@ pub unsafe fn main() {
     let checker = components::appid::checker::ProcessCheckerMachineComponent::new(checking_policy)
         .finalize(components::process_checker_machine_component_static!());
 
+    // Reject a process binary if checking one of its credentials takes
+    // longer than a second.
+    let checker_alarm = static_init!(
+        VirtualMuxAlarm<'static, nrf52840::rtc::Rtc>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    checker_alarm.setup();
+    components::appid::checker::ProcessCheckerMachineTimeoutComponent::new(
+        checker,
+        checker_alarm,
+        1000,
+    )
+    .finalize(
+        components::process_checker_machine_timeout_component_static!(
+            VirtualMuxAlarm<'static, nrf52840::rtc::Rtc>
+        ),
+    );
+
     // Create and start the asynchronous process loader.
     let _loader = components::loader::sequential::ProcessLoaderSequentialComponent::new(
         checker,
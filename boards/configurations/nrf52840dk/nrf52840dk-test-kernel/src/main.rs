@@ -92,10 +92,12 @@ impl TestLauncher {
         match index {
             0 => unsafe { test::sha256_test::run_sha256(self) },
             1 => unsafe { test::hmac_sha256_test::run_hmacsha256(self) },
-            2 => unsafe { test::siphash24_test::run_siphash24(self) },
-            3 => unsafe { test::aes_test::run_aes128_ctr(&self.peripherals.ecb, self) },
-            4 => unsafe { test::aes_test::run_aes128_cbc(&self.peripherals.ecb, self) },
-            5 => unsafe { test::aes_test::run_aes128_ecb(&self.peripherals.ecb, self) },
+            2 => unsafe { test::sha512_test::run_sha512(self) },
+            3 => unsafe { test::hmac_sha512_test::run_hmacsha512(self) },
+            4 => unsafe { test::siphash24_test::run_siphash24(self) },
+            5 => unsafe { test::aes_test::run_aes128_ctr(&self.peripherals.ecb, self) },
+            6 => unsafe { test::aes_test::run_aes128_cbc(&self.peripherals.ecb, self) },
+            7 => unsafe { test::aes_test::run_aes128_ecb(&self.peripherals.ecb, self) },
             _ => kernel::debug!("All tests finished."),
         }
     }
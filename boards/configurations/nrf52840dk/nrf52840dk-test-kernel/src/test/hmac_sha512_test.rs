@@ -0,0 +1,62 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright Tock Contributors 2024.
+
+//! This tests a software HMAC-SHA512 implementation.
+
+use core::ptr::{addr_of, addr_of_mut};
+
+use capsules_core::test::capsule_test::{CapsuleTest, CapsuleTestClient};
+use capsules_extra::hmac_sha512::HmacSha512Software;
+use capsules_extra::sha512::Sha512Software;
+use capsules_extra::test::hmac_sha512::TestHmacSha512;
+use kernel::deferred_call::DeferredCallClient;
+use kernel::static_init;
+
+pub unsafe fn run_hmacsha512(client: &'static dyn CapsuleTestClient) {
+    let t = static_init_test_hmacsha512(client);
+    t.run();
+}
+
+pub static mut DIGEST_DATA: [u8; 64] = [0; 64];
+
+// Test case 2 from RFC 4231.
+pub static mut RFC_STR: [u8; 28] = *b"what do ya want for nothing?";
+pub static mut RFC_KEY: [u8; 4] = *b"Jefe";
+pub static mut RFC_HMAC: [u8; 64] = [
+    0x16, 0x4b, 0x7a, 0x7b, 0xfc, 0xf8, 0x19, 0xe2, 0xe3, 0x95, 0xfb, 0xe7, 0x3b, 0x56, 0xe0, 0xa3,
+    0x87, 0xbd, 0x64, 0x22, 0x2e, 0x83, 0x1f, 0xd6, 0x10, 0x27, 0x0c, 0xd7, 0xea, 0x25, 0x05, 0x54,
+    0x97, 0x58, 0xbf, 0x75, 0xc0, 0x5a, 0x99, 0x4a, 0x6d, 0x03, 0x4f, 0x65, 0xf8, 0xf0, 0xe6, 0xfd,
+    0xca, 0xea, 0xb1, 0xa3, 0x4d, 0x4a, 0x6b, 0x4b, 0x63, 0x6e, 0x07, 0x0a, 0x38, 0xbc, 0xe7, 0x37,
+];
+
+unsafe fn static_init_test_hmacsha512(
+    client: &'static dyn CapsuleTestClient,
+) -> &'static TestHmacSha512 {
+    let sha512_hash_buf = static_init!([u8; 128], [0; 128]);
+
+    let sha512 = static_init!(Sha512Software<'static>, Sha512Software::new());
+    sha512.register();
+
+    let hmacsha512_verify_buf = static_init!([u8; 64], [0; 64]);
+
+    let hmacsha512 = static_init!(
+        HmacSha512Software<'static, Sha512Software<'static>>,
+        HmacSha512Software::new(sha512, sha512_hash_buf, hmacsha512_verify_buf)
+    );
+    kernel::hil::digest::Digest::set_client(sha512, hmacsha512);
+
+    let test = static_init!(
+        TestHmacSha512,
+        TestHmacSha512::new(
+            hmacsha512,
+            &mut *addr_of_mut!(RFC_KEY),
+            &mut *addr_of_mut!(RFC_STR),
+            &mut *addr_of_mut!(DIGEST_DATA),
+            &*addr_of!(RFC_HMAC)
+        )
+    );
+    test.set_client(client);
+
+    test
+}
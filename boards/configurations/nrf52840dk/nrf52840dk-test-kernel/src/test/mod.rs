@@ -4,5 +4,7 @@
 
 pub(crate) mod aes_test;
 pub(crate) mod hmac_sha256_test;
+pub(crate) mod hmac_sha512_test;
 pub(crate) mod sha256_test;
+pub(crate) mod sha512_test;
 pub(crate) mod siphash24_test;
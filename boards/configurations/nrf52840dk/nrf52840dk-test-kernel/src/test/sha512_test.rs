@@ -0,0 +1,55 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright Tock Contributors 2024.
+
+//! This tests a software SHA512 implementation.
+//!
+//! This test uses a deferred call (for callbacks). It hashes the two block
+//! message from the FIPS 180-2 SHA-512 examples and uses Digest::verify to
+//! check that the hash is correct.
+//!
+//! The expected output is
+//! Sha512Test: Verification result: Ok(true)
+
+use core::ptr::addr_of_mut;
+
+use capsules_core::test::capsule_test::{CapsuleTest, CapsuleTestClient};
+use capsules_extra::sha512::Sha512Software;
+use capsules_extra::test::sha512::TestSha512;
+use kernel::static_init;
+
+pub unsafe fn run_sha512(client: &'static dyn CapsuleTestClient) {
+    let t = static_init_test_sha512(client);
+    t.run();
+}
+
+// NIST_STRING is the 896 bit message from FIPS 180-2, appendix C.2, and
+// NIST_HASH is its SHA-512 hash.
+pub static mut NIST_STRING: [u8; 112] =
+    *b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmn\
+hijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu";
+pub static mut NIST_HASH: [u8; 64] = [
+    0x8e, 0x95, 0x9b, 0x75, 0xda, 0xe3, 0x13, 0xda, 0x8c, 0xf4, 0xf7, 0x28, 0x14, 0xfc, 0x14, 0x3f,
+    0x8f, 0x77, 0x79, 0xc6, 0xeb, 0x9f, 0x7f, 0xa1, 0x72, 0x99, 0xae, 0xad, 0xb6, 0x88, 0x90, 0x18,
+    0x50, 0x1d, 0x28, 0x9e, 0x49, 0x00, 0xf7, 0xe4, 0x33, 0x1b, 0x99, 0xde, 0xc4, 0xb5, 0x43, 0x3a,
+    0xc7, 0xd3, 0x29, 0xee, 0xb6, 0xdd, 0x26, 0x54, 0x5e, 0x96, 0xe5, 0x5b, 0x87, 0x4b, 0xe9, 0x09,
+];
+
+unsafe fn static_init_test_sha512(client: &'static dyn CapsuleTestClient) -> &'static TestSha512 {
+    let sha = static_init!(Sha512Software<'static>, Sha512Software::new());
+    kernel::deferred_call::DeferredCallClient::register(sha);
+
+    // We expect NIST_STRING to hash to NIST_HASH, so final argument is true
+    let test = static_init!(
+        TestSha512,
+        TestSha512::new(
+            sha,
+            &mut *addr_of_mut!(NIST_STRING),
+            &mut *addr_of_mut!(NIST_HASH),
+            true
+        )
+    );
+    test.set_client(client);
+
+    test
+}
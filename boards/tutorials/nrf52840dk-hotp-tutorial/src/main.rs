@@ -20,12 +20,19 @@ use kernel::{capabilities, create_capability};
 use nrf52840::gpio::Pin;
 use nrf52840dk_lib::{self, PROCESSES};
 
+#[cfg(feature = "screen_tests")]
+mod test;
+
 // State for loading and holding applications.
 // How should the kernel respond when a process faults.
 const FAULT_RESPONSE: capsules_system::process_policies::PanicFaultPolicy =
     capsules_system::process_policies::PanicFaultPolicy {};
 
 // Screen
+#[cfg(feature = "screen_ssd1306")]
+type ScreenHw = components::ssd1306::Ssd1306ComponentType<nrf52840::i2c::TWI<'static>>;
+#[cfg(feature = "screen_sh1106")]
+type ScreenHw = components::sh1106::Sh1106ComponentType<nrf52840::i2c::TWI<'static>>;
 type ScreenDriver = components::screen::ScreenComponentType;
 
 // USB Keyboard HID - for nRF52840dk
@@ -147,12 +154,18 @@ pub unsafe fn main() {
 
     // Create the ssd1306 object for the actual screen driver.
     #[cfg(feature = "screen_ssd1306")]
-    let ssd1306_sh1106 = components::ssd1306::Ssd1306Component::new(ssd1306_sh1106_i2c, true)
-        .finalize(components::ssd1306_component_static!(nrf52840::i2c::TWI));
+    let ssd1306_sh1106: &ScreenHw =
+        components::ssd1306::Ssd1306Component::new(ssd1306_sh1106_i2c, true)
+            .finalize(components::ssd1306_component_static!(nrf52840::i2c::TWI));
 
     #[cfg(feature = "screen_sh1106")]
-    let ssd1306_sh1106 = components::sh1106::Sh1106Component::new(ssd1306_sh1106_i2c, true)
-        .finalize(components::sh1106_component_static!(nrf52840::i2c::TWI));
+    let ssd1306_sh1106: &ScreenHw = components::sh1106::Sh1106Component::new(
+        ssd1306_sh1106_i2c,
+        true,
+        // The 128 pixel wide panel starts at column 2 of the SH1106 RAM.
+        capsules_extra::sh1106::DEFAULT_COLUMN_OFFSET,
+    )
+    .finalize(components::sh1106_component_static!(nrf52840::i2c::TWI));
 
     let screen = components::screen::ScreenComponent::new(
         board_kernel,
@@ -162,6 +175,11 @@ pub unsafe fn main() {
     )
     .finalize(components::screen_component_static!(1032));
 
+    // Check the screen wiring by drawing test patterns instead of
+    // connecting the screen to the syscall driver.
+    #[cfg(feature = "screen_tests")]
+    test::screen_test::run(ssd1306_sh1106);
+
     ssd1306_sh1106.init_screen();
 
     //--------------------------------------------------------------------------
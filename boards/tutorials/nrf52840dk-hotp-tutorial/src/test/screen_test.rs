@@ -0,0 +1,168 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright Tock Contributors 2024.
+
+//! Test that the screen is wired up correctly.
+//!
+//! This clears the screen and then draws test patterns through the screen HIL,
+//! exercising `set_write_frame()` and `write()` end to end:
+//!
+//! 1. A frame around the edge of the panel. All four sides must be visible
+//!    and unbroken. On a SH1106 with the wrong column offset the left or right
+//!    side is missing and a short seam appears, as columns wrap around.
+//! 2. A checkerboard of 8x8 pixel squares across the whole panel.
+//!
+//! The test takes over the screen's client, so the screen syscall driver does
+//! not work while it is enabled. To run this test, build the board with the
+//! `screen_tests` feature, which runs
+//!
+//! ```rust,ignore
+//! test::screen_test::run(ssd1306_sh1106);
+//! ```
+//!
+//! before the screen is initialized.
+
+use core::cell::Cell;
+
+use kernel::debug;
+use kernel::hil::screen::{Screen, ScreenClient, ScreenPixelFormat};
+use kernel::static_init;
+use kernel::utilities::cells::TakeCell;
+use kernel::utilities::leasable_buffer::SubSliceMut;
+use kernel::ErrorCode;
+
+use crate::ScreenHw;
+
+/// Size of a frame of the 128x64 monochrome screen, in bytes.
+const FRAME_SIZE: usize = 128 * 64 / 8;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Step {
+    Clear,
+    Border,
+    Pattern,
+    Done,
+}
+
+struct TestScreen<'a, S: Screen<'a>> {
+    screen: &'a S,
+    buffer: TakeCell<'static, [u8]>,
+    step: Cell<Step>,
+}
+
+impl<'a, S: Screen<'a>> TestScreen<'a, S> {
+    /// Fill the buffer for the current step and start writing the frame.
+    fn start_step(&self) {
+        let (width, height) = self.screen.get_resolution();
+        let step = self.step.get();
+        if step == Step::Done {
+            debug!("Screen test: done");
+            return;
+        }
+
+        self.buffer.map(|buffer| match step {
+            Step::Clear => buffer.fill(0),
+            Step::Border => {
+                let pages = height / 8;
+                for (i, byte) in buffer.iter_mut().enumerate() {
+                    let column = i % width;
+                    let page = i / width;
+                    *byte = if column == 0 || column == width - 1 {
+                        0xff
+                    } else if page == 0 {
+                        0x01
+                    } else if page == pages - 1 {
+                        0x80
+                    } else {
+                        0
+                    };
+                }
+            }
+            Step::Pattern => {
+                // Each byte covers eight rows of one column (one page).
+                for (i, byte) in buffer.iter_mut().enumerate() {
+                    let column = i % width;
+                    let page = i / width;
+                    *byte = if (column / 8 + page) % 2 == 0 {
+                        0xff
+                    } else {
+                        0
+                    };
+                }
+            }
+            Step::Done => {}
+        });
+
+        if let Err(e) = self.screen.set_write_frame(0, 0, width, height) {
+            self.fail("set_write_frame", e);
+        }
+    }
+
+    fn fail(&self, operation: &str, error: ErrorCode) {
+        debug!("Screen test: {} failed: {:?}", operation, error);
+        self.step.set(Step::Done);
+    }
+}
+
+impl<'a, S: Screen<'a>> ScreenClient for TestScreen<'a, S> {
+    fn command_complete(&self, result: Result<(), ErrorCode>) {
+        if let Err(e) = result {
+            return self.fail("set_write_frame", e);
+        }
+
+        // The write frame is set, write the frame data.
+        if let Some(buffer) = self.buffer.take() {
+            if let Err(e) = self.screen.write(SubSliceMut::new(buffer), false) {
+                self.fail("write", e);
+            }
+        }
+    }
+
+    fn write_complete(&self, data: SubSliceMut<'static, u8>, result: Result<(), ErrorCode>) {
+        self.buffer.replace(data.take());
+        if let Err(e) = result {
+            return self.fail("write", e);
+        }
+
+        match self.step.get() {
+            Step::Clear => {
+                debug!("Screen test: cleared");
+                self.step.set(Step::Border);
+            }
+            Step::Border => {
+                debug!("Screen test: drew border");
+                self.step.set(Step::Pattern);
+            }
+            Step::Pattern => {
+                debug!("Screen test: drew checkerboard");
+                self.step.set(Step::Done);
+            }
+            Step::Done => {}
+        }
+        self.start_step();
+    }
+
+    fn screen_is_ready(&self) {
+        debug!("Screen test: screen is ready");
+        self.start_step();
+    }
+}
+
+pub unsafe fn run(screen: &'static ScreenHw) {
+    let (width, height) = screen.get_resolution();
+    if screen.get_pixel_format() != ScreenPixelFormat::Mono || width * height / 8 != FRAME_SIZE {
+        debug!("Screen test: expected a 128x64 monochrome screen");
+        return;
+    }
+
+    let buffer = static_init!([u8; FRAME_SIZE], [0; FRAME_SIZE]);
+    let test = static_init!(
+        TestScreen<'static, ScreenHw>,
+        TestScreen {
+            screen,
+            buffer: TakeCell::new(buffer),
+            step: Cell::new(Step::Clear),
+        }
+    );
+    screen.set_client(test);
+}
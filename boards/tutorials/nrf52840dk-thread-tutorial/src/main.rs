@@ -145,8 +145,13 @@ pub unsafe fn main() {
         .finalize(components::ssd1306_component_static!(nrf52840::i2c::TWI));
 
     #[cfg(feature = "screen_sh1106")]
-    let ssd1306_sh1106 = components::sh1106::Sh1106Component::new(ssd1306_sh1106_i2c, true)
-        .finalize(components::sh1106_component_static!(nrf52840::i2c::TWI));
+    let ssd1306_sh1106 = components::sh1106::Sh1106Component::new(
+        ssd1306_sh1106_i2c,
+        true,
+        // The 128 pixel wide panel starts at column 2 of the SH1106 RAM.
+        capsules_extra::sh1106::DEFAULT_COLUMN_OFFSET,
+    )
+    .finalize(components::sh1106_component_static!(nrf52840::i2c::TWI));
 
     let screen = components::screen::ScreenComponent::new(
         board_kernel,
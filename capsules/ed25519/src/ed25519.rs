@@ -0,0 +1,178 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright Tock Contributors 2024.
+
+//! Ed25519 signature verifier.
+//!
+//! This implements the `SignatureVerify` HIL in software using the
+//! `ed25519-compact` crate.
+//!
+//! Ed25519 hashes the signed message internally, so there is no separate
+//! prehash step. The `HL` byte "hash" passed to `verify()` is the message the
+//! signature was computed over. When used with `AppCheckerSignature`, this is
+//! the digest of the integrity region computed by the checker's hasher.
+//!
+//! ### Usage
+//!
+//! ```rust,ignore
+//! let verifier = static_init!(
+//!     capsules_ed25519::ed25519::Ed25519SignatureVerifier<'static, 32>,
+//!     capsules_ed25519::ed25519::Ed25519SignatureVerifier::new(&PUBLIC_KEY)
+//! );
+//! verifier.register();
+//! ```
+
+use ed25519_compact::{PublicKey, Signature};
+use kernel::deferred_call::{DeferredCall, DeferredCallClient};
+use kernel::hil::public_key_crypto::signature::{ClientVerify, SignatureVerify};
+use kernel::utilities::cells::{OptionalCell, TakeCell};
+use kernel::ErrorCode;
+
+/// Verifies Ed25519 signatures over `HL` byte messages with a single
+/// public key.
+pub struct Ed25519SignatureVerifier<'a, const HL: usize> {
+    client: OptionalCell<&'a dyn ClientVerify<HL, 64>>,
+    public_key: PublicKey,
+    /// Result of the verification in progress, reported in the deferred call.
+    verified: OptionalCell<bool>,
+    hash_storage: TakeCell<'static, [u8; HL]>,
+    signature_storage: TakeCell<'static, [u8; 64]>,
+    deferred_call: DeferredCall,
+}
+
+impl<'a, const HL: usize> Ed25519SignatureVerifier<'a, HL> {
+    /// Create a verifier for the 32 byte Ed25519 public key `public_key`.
+    pub fn new(public_key: &[u8; 32]) -> Self {
+        Self {
+            client: OptionalCell::empty(),
+            public_key: PublicKey::new(*public_key),
+            verified: OptionalCell::empty(),
+            hash_storage: TakeCell::empty(),
+            signature_storage: TakeCell::empty(),
+            deferred_call: DeferredCall::new(),
+        }
+    }
+}
+
+impl<'a, const HL: usize> SignatureVerify<'a, HL, 64> for Ed25519SignatureVerifier<'a, HL> {
+    fn set_verify_client(&self, client: &'a dyn ClientVerify<HL, 64>) {
+        self.client.replace(client);
+    }
+
+    fn verify(
+        &self,
+        hash: &'static mut [u8; HL],
+        signature: &'static mut [u8; 64],
+    ) -> Result<(), (ErrorCode, &'static mut [u8; HL], &'static mut [u8; 64])> {
+        if self.verified.is_some() {
+            return Err((ErrorCode::BUSY, hash, signature));
+        }
+
+        let verified = self
+            .public_key
+            .verify(&hash[..], &Signature::new(*signature))
+            .is_ok();
+
+        self.verified.set(verified);
+        self.hash_storage.replace(hash);
+        self.signature_storage.replace(signature);
+        self.deferred_call.set();
+        Ok(())
+    }
+}
+
+impl<'a, const HL: usize> DeferredCallClient for Ed25519SignatureVerifier<'a, HL> {
+    fn handle_deferred_call(&self) {
+        if let Some(verified) = self.verified.take() {
+            if let (Some(hash), Some(signature)) =
+                (self.hash_storage.take(), self.signature_storage.take())
+            {
+                self.client.map(|client| {
+                    client.verification_done(Ok(verified), hash, signature);
+                });
+            }
+        }
+    }
+
+    fn register(&'static self) {
+        self.deferred_call.register(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::cell::Cell;
+    use std::boxed::Box;
+
+    // RFC 8032, section 7.1, TEST 2.
+    const PUBLIC_KEY: [u8; 32] = [
+        0x3d, 0x40, 0x17, 0xc3, 0xe8, 0x43, 0x89, 0x5a, 0x92, 0xb7, 0x0a, 0xa7, 0x4d, 0x1b, 0x7e,
+        0xbc, 0x9c, 0x98, 0x2c, 0xcf, 0x2e, 0xc4, 0x96, 0x8c, 0xc0, 0xcd, 0x55, 0xf1, 0x2a, 0xf4,
+        0x66, 0x0c,
+    ];
+    const MESSAGE: [u8; 1] = [0x72];
+    const SIGNATURE: [u8; 64] = [
+        0x92, 0xa0, 0x09, 0xa9, 0xf0, 0xd4, 0xca, 0xb8, 0x72, 0x0e, 0x82, 0x0b, 0x5f, 0x64, 0x25,
+        0x40, 0xa2, 0xb2, 0x7b, 0x54, 0x16, 0x50, 0x3f, 0x8f, 0xb3, 0x76, 0x22, 0x23, 0xeb, 0xdb,
+        0x69, 0xda, 0x08, 0x5a, 0xc1, 0xe4, 0x3e, 0x15, 0x99, 0x6e, 0x45, 0x8f, 0x36, 0x13, 0xd0,
+        0xf1, 0x1d, 0x8c, 0x38, 0x7b, 0x2e, 0xae, 0xb4, 0x30, 0x2a, 0xee, 0xb0, 0x0d, 0x29, 0x16,
+        0x12, 0xbb, 0x0c, 0x00,
+    ];
+
+    struct RecordingClient {
+        result: Cell<Option<Result<bool, ErrorCode>>>,
+    }
+
+    impl ClientVerify<1, 64> for RecordingClient {
+        fn verification_done(
+            &self,
+            result: Result<bool, ErrorCode>,
+            _hash: &'static mut [u8; 1],
+            _signature: &'static mut [u8; 64],
+        ) {
+            self.result.set(Some(result));
+        }
+    }
+
+    fn verify(message: [u8; 1], signature: [u8; 64]) -> Option<Result<bool, ErrorCode>> {
+        let verifier: &'static Ed25519SignatureVerifier<'static, 1> =
+            Box::leak(Box::new(Ed25519SignatureVerifier::new(&PUBLIC_KEY)));
+        let client: &'static RecordingClient = Box::leak(Box::new(RecordingClient {
+            result: Cell::new(None),
+        }));
+        verifier.set_verify_client(client);
+
+        assert!(verifier
+            .verify(Box::leak(Box::new(message)), Box::leak(Box::new(signature)))
+            .is_ok());
+        // A second request while the first is pending is rejected.
+        assert!(matches!(
+            verifier.verify(Box::leak(Box::new(message)), Box::leak(Box::new(signature))),
+            Err((ErrorCode::BUSY, _, _))
+        ));
+        assert_eq!(client.result.get(), None);
+
+        verifier.handle_deferred_call();
+        client.result.get()
+    }
+
+    #[test]
+    fn rfc8032_signature_verifies() {
+        assert_eq!(verify(MESSAGE, SIGNATURE), Some(Ok(true)));
+    }
+
+    #[test]
+    fn modified_message_does_not_verify() {
+        assert_eq!(verify([0x73], SIGNATURE), Some(Ok(false)));
+    }
+
+    #[test]
+    fn modified_signature_does_not_verify() {
+        let mut signature = SIGNATURE;
+        signature[0] ^= 1;
+        assert_eq!(verify(MESSAGE, signature), Some(Ok(false)));
+    }
+}
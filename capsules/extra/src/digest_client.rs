@@ -0,0 +1,71 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright Tock Contributors 2024.
+
+//! Client storage for digest implementations.
+
+use kernel::hil::digest::{Client, ClientData, ClientHash, ClientVerify};
+use kernel::hil::digest::{ClientDataHash, ClientDataVerify};
+use kernel::utilities::leasable_buffer::{SubSlice, SubSliceMut};
+use kernel::ErrorCode;
+
+/// The client of a digest engine.
+///
+/// Each `set_client` variant of the digest HIL provides a different client
+/// trait object. Trait objects cannot be converted to the traits they extend,
+/// so the client is stored as whichever object was provided, and callbacks
+/// that the client does not implement are dropped. Setting a client replaces
+/// the previous one.
+#[derive(Clone, Copy)]
+pub enum DigestClient<'a, const L: usize> {
+    Digest(&'a dyn Client<L>),
+    DataHash(&'a dyn ClientDataHash<L>),
+    DataVerify(&'a dyn ClientDataVerify<L>),
+    Data(&'a dyn ClientData<L>),
+    Hash(&'a dyn ClientHash<L>),
+    Verify(&'a dyn ClientVerify<L>),
+}
+
+impl<'a, const L: usize> DigestClient<'a, L> {
+    pub fn add_data_done(&self, result: Result<(), ErrorCode>, data: SubSlice<'static, u8>) {
+        match self {
+            DigestClient::Digest(c) => c.add_data_done(result, data),
+            DigestClient::DataHash(c) => c.add_data_done(result, data),
+            DigestClient::DataVerify(c) => c.add_data_done(result, data),
+            DigestClient::Data(c) => c.add_data_done(result, data),
+            DigestClient::Hash(_) | DigestClient::Verify(_) => {}
+        }
+    }
+
+    pub fn add_mut_data_done(&self, result: Result<(), ErrorCode>, data: SubSliceMut<'static, u8>) {
+        match self {
+            DigestClient::Digest(c) => c.add_mut_data_done(result, data),
+            DigestClient::DataHash(c) => c.add_mut_data_done(result, data),
+            DigestClient::DataVerify(c) => c.add_mut_data_done(result, data),
+            DigestClient::Data(c) => c.add_mut_data_done(result, data),
+            DigestClient::Hash(_) | DigestClient::Verify(_) => {}
+        }
+    }
+
+    pub fn hash_done(&self, result: Result<(), ErrorCode>, digest: &'static mut [u8; L]) {
+        match self {
+            DigestClient::Digest(c) => c.hash_done(result, digest),
+            DigestClient::DataHash(c) => c.hash_done(result, digest),
+            DigestClient::Hash(c) => c.hash_done(result, digest),
+            DigestClient::DataVerify(_) | DigestClient::Data(_) | DigestClient::Verify(_) => {}
+        }
+    }
+
+    pub fn verification_done(
+        &self,
+        result: Result<bool, ErrorCode>,
+        compare: &'static mut [u8; L],
+    ) {
+        match self {
+            DigestClient::Digest(c) => c.verification_done(result, compare),
+            DigestClient::DataVerify(c) => c.verification_done(result, compare),
+            DigestClient::Verify(c) => c.verification_done(result, compare),
+            DigestClient::DataHash(_) | DigestClient::Data(_) | DigestClient::Hash(_) => {}
+        }
+    }
+}
@@ -0,0 +1,565 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright Tock Contributors 2024.
+
+//! Feed data to a digest engine in fixed-size chunks.
+//!
+//! Some digest engines can only process a limited amount of data per
+//! `add_data()` or `add_mut_data()` call, for example because they use DMA
+//! with a limited window. `DigestStreamer` wraps such an engine and implements
+//! the digest HILs itself. Data added to the streamer is passed to the engine
+//! in sub-slices of at most `chunk_size` bytes, advancing on each
+//! `add_data_done()`/`add_mut_data_done()` callback. The client receives a
+//! single callback with its original buffer once all of the data has been
+//! added, and `run()`/`verify()` are rejected with `BUSY` until then.
+//!
+//! This lets a client such as `AppCheckerSignature` hash an entire process
+//! binary with one `add_data()` call regardless of the engine's limits.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust,ignore
+//! let streamer = static_init!(
+//!     capsules_extra::digest_streamer::DigestStreamer<'static, Sha, 32>,
+//!     capsules_extra::digest_streamer::DigestStreamer::new(sha, 1024)
+//! );
+//! kernel::hil::digest::Digest::set_client(sha, streamer);
+//! ```
+
+use core::cell::Cell;
+use core::ops::Range;
+
+use kernel::hil::digest;
+use kernel::utilities::cells::OptionalCell;
+use kernel::utilities::leasable_buffer::SubSlice;
+use kernel::utilities::leasable_buffer::SubSliceMut;
+use kernel::utilities::leasable_buffer::SubSliceMutImmut;
+use kernel::ErrorCode;
+
+use crate::digest_client::DigestClient;
+
+pub struct DigestStreamer<'a, D: digest::Digest<'a, L>, const L: usize> {
+    digest: &'a D,
+    /// Maximum number of bytes passed to the digest engine at once.
+    chunk_size: usize,
+    client: OptionalCell<DigestClient<'a, L>>,
+    /// Start and end of the client's buffer window, relative to the start of
+    /// the underlying buffer.
+    window: Cell<(usize, usize)>,
+    /// Number of bytes of the client's window already added.
+    offset: Cell<usize>,
+    /// Whether data is currently being added.
+    busy: Cell<bool>,
+}
+
+impl<'a, D: digest::Digest<'a, L>, const L: usize> DigestStreamer<'a, D, L> {
+    pub fn new(digest: &'a D, chunk_size: usize) -> Self {
+        Self {
+            digest,
+            chunk_size: core::cmp::max(chunk_size, 1),
+            client: OptionalCell::empty(),
+            window: Cell::new((0, 0)),
+            offset: Cell::new(0),
+            busy: Cell::new(false),
+        }
+    }
+
+    /// Record the window of the client's buffer, which starts at `active` in
+    /// the underlying buffer starting at `full`, so it can be restored once
+    /// all of the data has been added.
+    fn start(&self, active: *const u8, full: *const u8, len: usize) {
+        let start = active as usize - full as usize;
+        self.window.set((start, start + len));
+        self.offset.set(0);
+        self.busy.set(true);
+    }
+
+    /// The range of the next chunk to add, relative to the start of the
+    /// underlying buffer.
+    fn next_chunk(&self) -> (usize, usize) {
+        let (start, end) = self.window.get();
+        let chunk_start = start + self.offset.get();
+        (
+            chunk_start,
+            core::cmp::min(chunk_start + self.chunk_size, end),
+        )
+    }
+
+    /// Record that the data up to `chunk_end` is being passed to the digest
+    /// engine. This happens before the engine is called, as it may call back
+    /// synchronously.
+    fn advance(&self, chunk_end: usize) {
+        let (start, _) = self.window.get();
+        self.offset.set(chunk_end - start);
+    }
+
+    /// Finish adding data, returning the client's original buffer window.
+    fn finish(&self) -> Range<usize> {
+        let (start, end) = self.window.get();
+        self.busy.set(false);
+        start..end
+    }
+
+    /// Pass the next chunk of the client's buffer to the digest engine.
+    fn add_next(
+        &self,
+        mut data: SubSliceMutImmut<'static, u8>,
+    ) -> Result<(), (ErrorCode, SubSliceMutImmut<'static, u8>)> {
+        let (chunk_start, chunk_end) = self.next_chunk();
+        data.reset();
+        data.slice(chunk_start..chunk_end);
+        self.advance(chunk_end);
+
+        let result = match data {
+            SubSliceMutImmut::Immutable(buf) => self
+                .digest
+                .add_data(buf)
+                .map_err(|(e, buf)| (e, SubSliceMutImmut::Immutable(buf))),
+            SubSliceMutImmut::Mutable(buf) => self
+                .digest
+                .add_mut_data(buf)
+                .map_err(|(e, buf)| (e, SubSliceMutImmut::Mutable(buf))),
+        };
+        result.map_err(|(e, mut data)| {
+            data.reset();
+            data.slice(self.finish());
+            (e, data)
+        })
+    }
+
+    /// Continue after the digest engine finished adding a chunk.
+    fn chunk_done(&self, result: Result<(), ErrorCode>, mut data: SubSliceMutImmut<'static, u8>) {
+        let (start, end) = self.window.get();
+        let result = if result.is_ok() && start + self.offset.get() < end {
+            match self.add_next(data) {
+                Ok(()) => return,
+                Err((e, d)) => {
+                    data = d;
+                    Err(e)
+                }
+            }
+        } else {
+            data.reset();
+            data.slice(self.finish());
+            result
+        };
+
+        self.client.map(|client| match data {
+            SubSliceMutImmut::Immutable(buf) => client.add_data_done(result, buf),
+            SubSliceMutImmut::Mutable(buf) => client.add_mut_data_done(result, buf),
+        });
+    }
+}
+
+impl<'a, D: digest::Digest<'a, L>, const L: usize> digest::DigestData<'a, L>
+    for DigestStreamer<'a, D, L>
+{
+    fn add_data(
+        &self,
+        mut data: SubSlice<'static, u8>,
+    ) -> Result<(), (ErrorCode, SubSlice<'static, u8>)> {
+        if self.busy.get() {
+            return Err((ErrorCode::BUSY, data));
+        }
+
+        let (active, len) = (data.as_ptr(), data.len());
+        data.reset();
+        self.start(active, data.as_ptr(), len);
+        let (chunk_start, chunk_end) = self.next_chunk();
+        data.slice(chunk_start..chunk_end);
+        self.advance(chunk_end);
+        self.digest.add_data(data).map_err(|(e, mut data)| {
+            data.reset();
+            data.slice(self.finish());
+            (e, data)
+        })
+    }
+
+    fn add_mut_data(
+        &self,
+        mut data: SubSliceMut<'static, u8>,
+    ) -> Result<(), (ErrorCode, SubSliceMut<'static, u8>)> {
+        if self.busy.get() {
+            return Err((ErrorCode::BUSY, data));
+        }
+
+        let (active, len) = (data.as_ptr(), data.len());
+        data.reset();
+        self.start(active, data.as_ptr(), len);
+        let (chunk_start, chunk_end) = self.next_chunk();
+        data.slice(chunk_start..chunk_end);
+        self.advance(chunk_end);
+        self.digest.add_mut_data(data).map_err(|(e, mut data)| {
+            data.reset();
+            data.slice(self.finish());
+            (e, data)
+        })
+    }
+
+    fn clear_data(&self) {
+        self.digest.clear_data();
+    }
+
+    fn set_data_client(&'a self, client: &'a dyn digest::ClientData<L>) {
+        self.client.set(DigestClient::Data(client));
+    }
+}
+
+impl<'a, D: digest::Digest<'a, L>, const L: usize> digest::DigestHash<'a, L>
+    for DigestStreamer<'a, D, L>
+{
+    fn run(
+        &'a self,
+        digest: &'static mut [u8; L],
+    ) -> Result<(), (ErrorCode, &'static mut [u8; L])> {
+        if self.busy.get() {
+            Err((ErrorCode::BUSY, digest))
+        } else {
+            self.digest.run(digest)
+        }
+    }
+
+    fn set_hash_client(&'a self, client: &'a dyn digest::ClientHash<L>) {
+        self.client.set(DigestClient::Hash(client));
+    }
+}
+
+impl<'a, D: digest::Digest<'a, L>, const L: usize> digest::DigestVerify<'a, L>
+    for DigestStreamer<'a, D, L>
+{
+    fn verify(
+        &'a self,
+        compare: &'static mut [u8; L],
+    ) -> Result<(), (ErrorCode, &'static mut [u8; L])> {
+        if self.busy.get() {
+            Err((ErrorCode::BUSY, compare))
+        } else {
+            self.digest.verify(compare)
+        }
+    }
+
+    fn set_verify_client(&'a self, client: &'a dyn digest::ClientVerify<L>) {
+        self.client.set(DigestClient::Verify(client));
+    }
+}
+
+impl<'a, D: digest::Digest<'a, L>, const L: usize> digest::Digest<'a, L>
+    for DigestStreamer<'a, D, L>
+{
+    fn set_client(&'a self, client: &'a dyn digest::Client<L>) {
+        self.client.set(DigestClient::Digest(client));
+    }
+}
+
+impl<'a, D: digest::Digest<'a, L>, const L: usize> digest::DigestDataHash<'a, L>
+    for DigestStreamer<'a, D, L>
+{
+    fn set_client(&'a self, client: &'a dyn digest::ClientDataHash<L>) {
+        self.client.set(DigestClient::DataHash(client));
+    }
+}
+
+impl<'a, D: digest::Digest<'a, L>, const L: usize> digest::DigestDataVerify<'a, L>
+    for DigestStreamer<'a, D, L>
+{
+    fn set_client(&'a self, client: &'a dyn digest::ClientDataVerify<L>) {
+        self.client.set(DigestClient::DataVerify(client));
+    }
+}
+
+impl<'a, D: digest::Digest<'a, L>, const L: usize> digest::ClientData<L>
+    for DigestStreamer<'a, D, L>
+{
+    fn add_data_done(&self, result: Result<(), ErrorCode>, data: SubSlice<'static, u8>) {
+        self.chunk_done(result, SubSliceMutImmut::Immutable(data));
+    }
+
+    fn add_mut_data_done(&self, result: Result<(), ErrorCode>, data: SubSliceMut<'static, u8>) {
+        self.chunk_done(result, SubSliceMutImmut::Mutable(data));
+    }
+}
+
+impl<'a, D: digest::Digest<'a, L>, const L: usize> digest::ClientHash<L>
+    for DigestStreamer<'a, D, L>
+{
+    fn hash_done(&self, result: Result<(), ErrorCode>, digest: &'static mut [u8; L]) {
+        self.client.map(|client| client.hash_done(result, digest));
+    }
+}
+
+impl<'a, D: digest::Digest<'a, L>, const L: usize> digest::ClientVerify<L>
+    for DigestStreamer<'a, D, L>
+{
+    fn verification_done(&self, result: Result<bool, ErrorCode>, compare: &'static mut [u8; L]) {
+        self.client
+            .map(|client| client.verification_done(result, compare));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use kernel::hil::digest::{Digest, DigestData, DigestHash, DigestVerify};
+    use kernel::utilities::cells::MapCell;
+    use std::boxed::Box;
+    use std::vec::Vec;
+
+    const CHUNK_SIZE: usize = 4;
+
+    /// Digest engine that records the chunks it is given and completes them
+    /// when `complete()` is called, or immediately if `synchronous` is set.
+    /// Adding the chunk at index `fail_at` fails in the callback, and adding
+    /// the chunk at index `refuse_at` returns `BUSY`.
+    struct MockDigest {
+        chunks: MapCell<Vec<Vec<u8>>>,
+        pending: OptionalCell<SubSlice<'static, u8>>,
+        fail_at: Cell<Option<usize>>,
+        refuse_at: Cell<Option<usize>>,
+        synchronous: Cell<bool>,
+        client: OptionalCell<&'static dyn digest::Client<4>>,
+    }
+
+    impl MockDigest {
+        fn complete(&self) {
+            if let Some(data) = self.pending.take() {
+                let count = self.chunks.map_or(0, |chunks| chunks.len());
+                let result = if self.fail_at.get() == Some(count - 1) {
+                    Err(ErrorCode::FAIL)
+                } else {
+                    Ok(())
+                };
+                self.client.map(|c| c.add_data_done(result, data));
+            }
+        }
+
+        fn chunks(&self) -> Vec<Vec<u8>> {
+            self.chunks.map_or(Vec::new(), |chunks| chunks.clone())
+        }
+    }
+
+    impl DigestData<'static, 4> for MockDigest {
+        fn add_data(
+            &self,
+            data: SubSlice<'static, u8>,
+        ) -> Result<(), (ErrorCode, SubSlice<'static, u8>)> {
+            assert!(data.len() <= CHUNK_SIZE);
+            let count = self.chunks.map_or(0, |chunks| chunks.len());
+            if self.refuse_at.get() == Some(count) {
+                return Err((ErrorCode::BUSY, data));
+            }
+            self.chunks
+                .map(|chunks| chunks.push(data.as_slice().to_vec()));
+            self.pending.set(data);
+            if self.synchronous.get() {
+                self.complete();
+            }
+            Ok(())
+        }
+
+        fn add_mut_data(
+            &self,
+            data: SubSliceMut<'static, u8>,
+        ) -> Result<(), (ErrorCode, SubSliceMut<'static, u8>)> {
+            Err((ErrorCode::NOSUPPORT, data))
+        }
+
+        fn clear_data(&self) {}
+
+        fn set_data_client(&'static self, _client: &'static dyn digest::ClientData<4>) {}
+    }
+
+    impl DigestHash<'static, 4> for MockDigest {
+        fn run(
+            &'static self,
+            digest: &'static mut [u8; 4],
+        ) -> Result<(), (ErrorCode, &'static mut [u8; 4])> {
+            self.client.map(|c| c.hash_done(Ok(()), digest));
+            Ok(())
+        }
+
+        fn set_hash_client(&'static self, _client: &'static dyn digest::ClientHash<4>) {}
+    }
+
+    impl DigestVerify<'static, 4> for MockDigest {
+        fn verify(
+            &'static self,
+            compare: &'static mut [u8; 4],
+        ) -> Result<(), (ErrorCode, &'static mut [u8; 4])> {
+            Err((ErrorCode::NOSUPPORT, compare))
+        }
+
+        fn set_verify_client(&'static self, _client: &'static dyn digest::ClientVerify<4>) {}
+    }
+
+    impl Digest<'static, 4> for MockDigest {
+        fn set_client(&'static self, client: &'static dyn digest::Client<4>) {
+            self.client.set(client);
+        }
+    }
+
+    /// Client that only implements data and hash callbacks, and records the
+    /// buffers it gets back.
+    struct RecordingClient {
+        added: MapCell<Vec<(Result<(), ErrorCode>, Vec<u8>)>>,
+        hashed: Cell<usize>,
+    }
+
+    impl digest::ClientData<4> for RecordingClient {
+        fn add_data_done(&self, result: Result<(), ErrorCode>, data: SubSlice<'static, u8>) {
+            self.added
+                .map(|added| added.push((result, data.as_slice().to_vec())));
+        }
+
+        fn add_mut_data_done(
+            &self,
+            _result: Result<(), ErrorCode>,
+            _data: SubSliceMut<'static, u8>,
+        ) {
+        }
+    }
+
+    impl digest::ClientHash<4> for RecordingClient {
+        fn hash_done(&self, _result: Result<(), ErrorCode>, _digest: &'static mut [u8; 4]) {
+            self.hashed.set(self.hashed.get() + 1);
+        }
+    }
+
+    type TestStreamer = DigestStreamer<'static, MockDigest, 4>;
+
+    fn setup() -> (
+        &'static MockDigest,
+        &'static TestStreamer,
+        &'static RecordingClient,
+    ) {
+        let digest: &'static MockDigest = Box::leak(Box::new(MockDigest {
+            chunks: MapCell::new(Vec::new()),
+            pending: OptionalCell::empty(),
+            fail_at: Cell::new(None),
+            refuse_at: Cell::new(None),
+            synchronous: Cell::new(false),
+            client: OptionalCell::empty(),
+        }));
+        let streamer: &'static TestStreamer =
+            Box::leak(Box::new(DigestStreamer::new(digest, CHUNK_SIZE)));
+        let client: &'static RecordingClient = Box::leak(Box::new(RecordingClient {
+            added: MapCell::new(Vec::new()),
+            hashed: Cell::new(0),
+        }));
+        digest.set_client(streamer);
+        digest::DigestDataHash::set_client(streamer, client);
+        (digest, streamer, client)
+    }
+
+    static DATA: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+
+    #[test]
+    fn data_is_added_in_chunks() {
+        let (digest, streamer, client) = setup();
+        let mut data = SubSlice::new(&DATA);
+        data.slice(1..11);
+
+        assert!(streamer.add_data(data).is_ok());
+        for _ in 0..3 {
+            assert!(matches!(
+                streamer.run(Box::leak(Box::new([0; 4]))),
+                Err((ErrorCode::BUSY, _))
+            ));
+            digest.complete();
+        }
+        assert_eq!(digest.chunks(), [&DATA[1..5], &DATA[5..9], &DATA[9..11]]);
+        // The client gets its original window back once.
+        assert_eq!(
+            client.added.map(|added| added.clone()),
+            Some(std::vec![(Ok(()), DATA[1..11].to_vec())])
+        );
+
+        assert!(streamer.run(Box::leak(Box::new([0; 4]))).is_ok());
+        assert_eq!(client.hashed.get(), 1);
+    }
+
+    #[test]
+    fn data_ending_on_a_chunk_boundary() {
+        let (digest, streamer, client) = setup();
+        let mut data = SubSlice::new(&DATA);
+        data.slice(..2 * CHUNK_SIZE);
+
+        assert!(streamer.add_data(data).is_ok());
+        digest.complete();
+        digest.complete();
+        assert_eq!(digest.chunks(), [&DATA[..4], &DATA[4..8]]);
+        assert_eq!(
+            client.added.map(|added| added.clone()),
+            Some(std::vec![(Ok(()), DATA[..8].to_vec())])
+        );
+        // No empty chunk is added after the last one.
+        assert!(digest.pending.is_none());
+    }
+
+    #[test]
+    fn error_mid_stream_returns_the_buffer() {
+        let (digest, streamer, client) = setup();
+        digest.fail_at.set(Some(1));
+
+        assert!(streamer.add_data(SubSlice::new(&DATA)).is_ok());
+        digest.complete();
+        digest.complete();
+        assert_eq!(digest.chunks().len(), 2);
+        assert_eq!(
+            client.added.map(|added| added.clone()),
+            Some(std::vec![(Err(ErrorCode::FAIL), DATA.to_vec())])
+        );
+
+        // The streamer accepts data again.
+        digest.fail_at.set(None);
+        assert!(streamer.add_data(SubSlice::new(&DATA)).is_ok());
+    }
+
+    #[test]
+    fn synchronous_engine_adds_each_chunk_once() {
+        let (digest, streamer, client) = setup();
+        digest.synchronous.set(true);
+
+        assert!(streamer.add_data(SubSlice::new(&DATA)).is_ok());
+        assert_eq!(digest.chunks(), [&DATA[..4], &DATA[4..8], &DATA[8..]]);
+        assert_eq!(
+            client.added.map(|added| added.clone()),
+            Some(std::vec![(Ok(()), DATA.to_vec())])
+        );
+        assert!(streamer.run(Box::leak(Box::new([0; 4]))).is_ok());
+    }
+
+    #[test]
+    fn refused_chunk_mid_stream_returns_the_buffer() {
+        let (digest, streamer, client) = setup();
+        digest.refuse_at.set(Some(1));
+
+        assert!(streamer.add_data(SubSlice::new(&DATA)).is_ok());
+        digest.complete();
+        assert_eq!(digest.chunks().len(), 1);
+        assert_eq!(
+            client.added.map(|added| added.clone()),
+            Some(std::vec![(Err(ErrorCode::BUSY), DATA.to_vec())])
+        );
+
+        // The stream does not stall.
+        digest.refuse_at.set(None);
+        assert!(streamer.add_data(SubSlice::new(&DATA)).is_ok());
+    }
+
+    #[test]
+    fn unsupported_engine_call_returns_the_buffer() {
+        let (_digest, streamer, _client) = setup();
+        let buffer: &'static mut [u8] = Box::leak(Box::new([0; 8]));
+        let mut data = SubSliceMut::new(buffer);
+        data.slice(2..6);
+
+        match streamer.add_mut_data(data) {
+            Err((ErrorCode::NOSUPPORT, data)) => assert_eq!(data.len(), 4),
+            _ => panic!("add_mut_data should fail"),
+        }
+        assert!(streamer.add_data(SubSlice::new(&DATA)).is_ok());
+    }
+}
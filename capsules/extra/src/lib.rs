@@ -33,6 +33,8 @@ pub mod cycle_count;
 pub mod dac;
 pub mod date_time;
 pub mod debug_process_restart;
+pub mod digest_client;
+pub mod digest_streamer;
 pub mod eui64;
 pub mod fm25cl;
 pub mod ft6x06;
@@ -41,6 +43,7 @@ pub mod gpio_async;
 pub mod hd44780;
 pub mod hmac;
 pub mod hmac_sha256;
+pub mod hmac_sha512;
 pub mod hs3003;
 pub mod hts221;
 pub mod humidity;
@@ -77,6 +80,7 @@ pub mod read_only_state;
 pub mod rf233;
 pub mod rf233_const;
 pub mod screen;
+pub mod screen_adapters;
 pub mod screen_shared;
 pub mod sdcard;
 pub mod segger_rtt;
@@ -84,6 +88,7 @@ pub mod seven_segment;
 pub mod sh1106;
 pub mod sha;
 pub mod sha256;
+pub mod sha512;
 pub mod sht3x;
 pub mod sht4x;
 pub mod si7021;
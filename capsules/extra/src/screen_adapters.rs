@@ -0,0 +1,1376 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright Tock Contributors 2024.
+
+//! Adapters that present a `Screen` with different properties than the
+//! underlying screen.
+//!
+//! - `ScreenRotate`: presents the screen rotated by a fixed amount, for panels
+//!   that are mounted rotated.
+//! - `ScreenARGB8888ToRGB565`: presents an RGB_565 screen as an ARGB_8888
+//!   screen.
+//! - `ScreenSoftwareDim`: implements `set_brightness()` by scaling pixel
+//!   values for screens without brightness control.
+//! - `ScreenDoubleBuffer`: collects writes in a framebuffer and only updates
+//!   the screen when `present()` is called, so partial frames never appear.
+//!
+//! ### Usage
+//!
+//! ```rust,ignore
+//! let rotate_buffer = static_init!([u8; 128 * 64 * 2], [0; 128 * 64 * 2]);
+//! let rotated = static_init!(
+//!     capsules_extra::screen_adapters::ScreenRotate<'static, Screen>,
+//!     capsules_extra::screen_adapters::ScreenRotate::new(
+//!         screen,
+//!         ScreenRotation::Rotated90,
+//!         rotate_buffer
+//!     )
+//! );
+//! screen.set_client(rotated);
+//! rotated.register();
+//! ```
+
+use core::cell::Cell;
+
+use kernel::deferred_call::{DeferredCall, DeferredCallClient};
+use kernel::hil::screen::{Screen, ScreenClient, ScreenPixelFormat, ScreenRotation};
+use kernel::utilities::cells::{MapCell, OptionalCell, TakeCell};
+use kernel::utilities::leasable_buffer::SubSliceMut;
+use kernel::ErrorCode;
+
+/// Presents a screen rotated by `rotation`.
+///
+/// Pixels written to the adapter are reordered so that they appear at the
+/// rotated location on the underlying screen. Because pixels that are
+/// consecutive in a rotated write frame are not consecutive on the underlying
+/// screen, the adapter collects the pixels for the entire write frame in its
+/// buffer and only writes to the underlying screen once the frame is
+/// complete. Writes that do not complete the frame finish immediately. The
+/// buffer must therefore be large enough to hold the largest write frame.
+///
+/// Rotation is only supported for pixel formats that use a whole number of
+/// bytes per pixel.
+pub struct ScreenRotate<'a, S: Screen<'a>> {
+    screen: &'a S,
+    rotation: ScreenRotation,
+    client: OptionalCell<&'a dyn ScreenClient>,
+    /// Holds the write frame in the order of the underlying screen.
+    buffer: TakeCell<'static, [u8]>,
+    /// The client's buffer while it is being written.
+    client_buffer: MapCell<SubSliceMut<'static, u8>>,
+    /// Width and height of the write frame, in rotated coordinates.
+    frame_size: Cell<(usize, usize)>,
+    /// Number of pixels of the write frame written so far.
+    position: Cell<usize>,
+    deferred_call: DeferredCall,
+}
+
+impl<'a, S: Screen<'a>> ScreenRotate<'a, S> {
+    pub fn new(screen: &'a S, rotation: ScreenRotation, buffer: &'static mut [u8]) -> Self {
+        Self {
+            screen,
+            rotation,
+            client: OptionalCell::empty(),
+            buffer: TakeCell::new(buffer),
+            client_buffer: MapCell::empty(),
+            frame_size: Cell::new((0, 0)),
+            position: Cell::new(0),
+            deferred_call: DeferredCall::new(),
+        }
+    }
+
+    fn bytes_per_pixel(&self) -> Option<usize> {
+        let bits = self.screen.get_pixel_format().get_bits_per_pixel();
+        if bits % 8 == 0 {
+            Some(bits / 8)
+        } else {
+            None
+        }
+    }
+
+    /// Index within the underlying screen's write frame of the pixel at
+    /// `(column, row)` within the rotated write frame.
+    fn rotated_index(&self, column: usize, row: usize) -> usize {
+        let (width, height) = self.frame_size.get();
+        match self.rotation {
+            ScreenRotation::Normal => row * width + column,
+            ScreenRotation::Rotated90 => (width - 1 - column) * height + row,
+            ScreenRotation::Rotated180 => (height - 1 - row) * width + (width - 1 - column),
+            ScreenRotation::Rotated270 => column * height + (height - 1 - row),
+        }
+    }
+
+    /// Copy the pixels in `data` to their rotated positions in `buffer`,
+    /// starting at pixel `position` of the write frame.
+    fn copy_rotated(
+        &self,
+        data: &[u8],
+        buffer: &mut [u8],
+        position: usize,
+        bytes_per_pixel: usize,
+    ) {
+        let (width, _) = self.frame_size.get();
+        for (offset, pixel) in data.chunks_exact(bytes_per_pixel).enumerate() {
+            let index = position + offset;
+            let target = self.rotated_index(index % width, index / width) * bytes_per_pixel;
+            buffer[target..target + bytes_per_pixel].copy_from_slice(pixel);
+        }
+    }
+}
+
+impl<'a, S: Screen<'a>> Screen<'a> for ScreenRotate<'a, S> {
+    fn set_client(&self, client: &'a dyn ScreenClient) {
+        self.client.set(client);
+    }
+
+    fn get_resolution(&self) -> (usize, usize) {
+        let (width, height) = self.screen.get_resolution();
+        match self.rotation {
+            ScreenRotation::Normal | ScreenRotation::Rotated180 => (width, height),
+            ScreenRotation::Rotated90 | ScreenRotation::Rotated270 => (height, width),
+        }
+    }
+
+    fn get_pixel_format(&self) -> ScreenPixelFormat {
+        self.screen.get_pixel_format()
+    }
+
+    fn get_rotation(&self) -> ScreenRotation {
+        self.screen.get_rotation() + self.rotation
+    }
+
+    fn set_write_frame(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<(), ErrorCode> {
+        let (rotated_width, rotated_height) = self.get_resolution();
+        if x + width > rotated_width || y + height > rotated_height {
+            return Err(ErrorCode::INVAL);
+        }
+
+        let (screen_width, screen_height) = self.screen.get_resolution();
+        self.screen.set_write_frame(
+            match self.rotation {
+                ScreenRotation::Normal => x,
+                ScreenRotation::Rotated90 => y,
+                ScreenRotation::Rotated180 => screen_width - x - width,
+                ScreenRotation::Rotated270 => screen_width - y - height,
+            },
+            match self.rotation {
+                ScreenRotation::Normal => y,
+                ScreenRotation::Rotated90 => screen_height - x - width,
+                ScreenRotation::Rotated180 => screen_height - y - height,
+                ScreenRotation::Rotated270 => x,
+            },
+            match self.rotation {
+                ScreenRotation::Normal | ScreenRotation::Rotated180 => width,
+                ScreenRotation::Rotated90 | ScreenRotation::Rotated270 => height,
+            },
+            match self.rotation {
+                ScreenRotation::Normal | ScreenRotation::Rotated180 => height,
+                ScreenRotation::Rotated90 | ScreenRotation::Rotated270 => width,
+            },
+        )?;
+
+        self.frame_size.set((width, height));
+        self.position.set(0);
+        Ok(())
+    }
+
+    fn write(
+        &self,
+        mut buffer: SubSliceMut<'static, u8>,
+        continue_write: bool,
+    ) -> Result<(), ErrorCode> {
+        if self.rotation == ScreenRotation::Normal {
+            return self.screen.write(buffer, continue_write);
+        }
+        if self.client_buffer.is_some() {
+            return Err(ErrorCode::BUSY);
+        }
+        let bytes_per_pixel = self.bytes_per_pixel().ok_or(ErrorCode::NOSUPPORT)?;
+
+        let (width, height) = self.frame_size.get();
+        let frame_pixels = width * height;
+        let position = if continue_write {
+            self.position.get()
+        } else {
+            0
+        };
+        let pixels = buffer.len() / bytes_per_pixel;
+        if buffer.len() % bytes_per_pixel != 0 || position + pixels > frame_pixels {
+            return Err(ErrorCode::SIZE);
+        }
+
+        let rotate_buffer = self.buffer.take().ok_or(ErrorCode::BUSY)?;
+        if rotate_buffer.len() < frame_pixels * bytes_per_pixel {
+            self.buffer.replace(rotate_buffer);
+            return Err(ErrorCode::SIZE);
+        }
+
+        self.copy_rotated(buffer.as_slice(), rotate_buffer, position, bytes_per_pixel);
+        self.client_buffer.replace(buffer);
+
+        if position + pixels < frame_pixels {
+            // Wait for the rest of the frame before writing to the screen.
+            self.position.set(position + pixels);
+            self.buffer.replace(rotate_buffer);
+            self.deferred_call.set();
+            return Ok(());
+        }
+
+        self.position.set(0);
+        let mut frame = SubSliceMut::new(rotate_buffer);
+        frame.slice(..frame_pixels * bytes_per_pixel);
+        self.screen.write(frame, false).inspect_err(|_| {
+            // The write will not complete, so the client's buffer will not
+            // be returned.
+            self.client_buffer.take();
+        })
+    }
+
+    fn set_brightness(&self, brightness: u16) -> Result<(), ErrorCode> {
+        self.screen.set_brightness(brightness)
+    }
+
+    fn set_power(&self, enabled: bool) -> Result<(), ErrorCode> {
+        self.screen.set_power(enabled)
+    }
+
+    fn set_invert(&self, enabled: bool) -> Result<(), ErrorCode> {
+        self.screen.set_invert(enabled)
+    }
+}
+
+impl<'a, S: Screen<'a>> ScreenClient for ScreenRotate<'a, S> {
+    fn command_complete(&self, result: Result<(), ErrorCode>) {
+        self.client.map(|client| client.command_complete(result));
+    }
+
+    fn write_complete(&self, buffer: SubSliceMut<'static, u8>, result: Result<(), ErrorCode>) {
+        match self.client_buffer.take() {
+            Some(client_buffer) => {
+                self.buffer.replace(buffer.take());
+                self.client
+                    .map(|client| client.write_complete(client_buffer, result));
+            }
+            None => {
+                self.client
+                    .map(|client| client.write_complete(buffer, result));
+            }
+        }
+    }
+
+    fn screen_is_ready(&self) {
+        self.client.map(|client| client.screen_is_ready());
+    }
+
+    fn resolution_changed(&self, width: usize, height: usize) {
+        let (width, height) = match self.rotation {
+            ScreenRotation::Normal | ScreenRotation::Rotated180 => (width, height),
+            ScreenRotation::Rotated90 | ScreenRotation::Rotated270 => (height, width),
+        };
+        self.client
+            .map(|client| client.resolution_changed(width, height));
+    }
+}
+
+impl<'a, S: Screen<'a>> DeferredCallClient for ScreenRotate<'a, S> {
+    fn handle_deferred_call(&self) {
+        if let Some(client_buffer) = self.client_buffer.take() {
+            self.client
+                .map(|client| client.write_complete(client_buffer, Ok(())));
+        }
+    }
+
+    fn register(&'static self) {
+        self.deferred_call.register(self);
+    }
+}
+
+/// Presents an RGB_565 screen as an ARGB_8888 screen.
+///
+/// Each ARGB_8888 pixel (bytes alpha, red, green, blue) written to the adapter
+/// is converted to an RGB_565 pixel (big endian) by keeping the top 5, 6 and 5
+/// bits of the red, green and blue channels. The alpha channel is ignored.
+///
+/// The converted pixels are written to the underlying screen from the
+/// adapter's buffer. If a write does not fit in the buffer, it is written to
+/// the underlying screen in multiple parts. The client's buffer is returned
+/// once all parts have been written.
+///
+/// Writes fail with `NOSUPPORT` if the underlying screen's pixel format is not
+/// RGB_565.
+pub struct ScreenARGB8888ToRGB565<'a, S: Screen<'a>> {
+    screen: &'a S,
+    client: OptionalCell<&'a dyn ScreenClient>,
+    /// Holds the converted pixels.
+    buffer: TakeCell<'static, [u8]>,
+    /// The client's buffer while it is being written.
+    client_buffer: MapCell<SubSliceMut<'static, u8>>,
+    /// Number of bytes of the client's buffer written so far.
+    written: Cell<usize>,
+}
+
+impl<'a, S: Screen<'a>> ScreenARGB8888ToRGB565<'a, S> {
+    pub fn new(screen: &'a S, buffer: &'static mut [u8]) -> Self {
+        Self {
+            screen,
+            client: OptionalCell::empty(),
+            buffer: TakeCell::new(buffer),
+            client_buffer: MapCell::empty(),
+            written: Cell::new(0),
+        }
+    }
+
+    fn argb8888_to_rgb565(pixel: &[u8]) -> [u8; 2] {
+        let (red, green, blue) = (pixel[1] as u16, pixel[2] as u16, pixel[3] as u16);
+        (((red & 0xf8) << 8) | ((green & 0xfc) << 3) | (blue >> 3)).to_be_bytes()
+    }
+
+    /// Convert and write the next part of the client's buffer.
+    fn write_next(&self, buffer: &'static mut [u8], continue_write: bool) -> Result<(), ErrorCode> {
+        let written = self.written.get();
+        let length = self.client_buffer.map_or(0, |client_buffer| {
+            let pixels = &client_buffer.as_slice()[written..];
+            let mut length = 0;
+            for (pixel, converted) in pixels.chunks_exact(4).zip(buffer.chunks_exact_mut(2)) {
+                converted.copy_from_slice(&Self::argb8888_to_rgb565(pixel));
+                length += 2;
+            }
+            length
+        });
+        self.written.set(written + length * 2);
+
+        let mut data = SubSliceMut::new(buffer);
+        data.slice(..length);
+        self.screen.write(data, continue_write)
+    }
+}
+
+impl<'a, S: Screen<'a>> Screen<'a> for ScreenARGB8888ToRGB565<'a, S> {
+    fn set_client(&self, client: &'a dyn ScreenClient) {
+        self.client.set(client);
+    }
+
+    fn get_resolution(&self) -> (usize, usize) {
+        self.screen.get_resolution()
+    }
+
+    fn get_pixel_format(&self) -> ScreenPixelFormat {
+        ScreenPixelFormat::ARGB_8888
+    }
+
+    fn get_rotation(&self) -> ScreenRotation {
+        self.screen.get_rotation()
+    }
+
+    fn set_write_frame(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<(), ErrorCode> {
+        self.screen.set_write_frame(x, y, width, height)
+    }
+
+    fn write(
+        &self,
+        buffer: SubSliceMut<'static, u8>,
+        continue_write: bool,
+    ) -> Result<(), ErrorCode> {
+        if self.screen.get_pixel_format() != ScreenPixelFormat::RGB_565 {
+            return Err(ErrorCode::NOSUPPORT);
+        }
+        if self.client_buffer.is_some() {
+            return Err(ErrorCode::BUSY);
+        }
+        if buffer.len() % 4 != 0 {
+            return Err(ErrorCode::SIZE);
+        }
+        let converted = self.buffer.take().ok_or(ErrorCode::BUSY)?;
+        if converted.len() < 2 {
+            self.buffer.replace(converted);
+            return Err(ErrorCode::SIZE);
+        }
+
+        self.client_buffer.replace(buffer);
+        self.written.set(0);
+        self.write_next(converted, continue_write).inspect_err(|_| {
+            // The write will not complete, so the client's buffer will not
+            // be returned.
+            self.client_buffer.take();
+        })
+    }
+
+    fn set_brightness(&self, brightness: u16) -> Result<(), ErrorCode> {
+        self.screen.set_brightness(brightness)
+    }
+
+    fn set_power(&self, enabled: bool) -> Result<(), ErrorCode> {
+        self.screen.set_power(enabled)
+    }
+
+    fn set_invert(&self, enabled: bool) -> Result<(), ErrorCode> {
+        self.screen.set_invert(enabled)
+    }
+}
+
+impl<'a, S: Screen<'a>> ScreenClient for ScreenARGB8888ToRGB565<'a, S> {
+    fn command_complete(&self, result: Result<(), ErrorCode>) {
+        self.client.map(|client| client.command_complete(result));
+    }
+
+    fn write_complete(&self, buffer: SubSliceMut<'static, u8>, result: Result<(), ErrorCode>) {
+        let remaining = self
+            .client_buffer
+            .map_or(0, |client_buffer| client_buffer.len() - self.written.get());
+
+        let result = if result.is_ok() && remaining > 0 {
+            match self.write_next(buffer.take(), true) {
+                // Wait for the next part to be written.
+                Ok(()) => return,
+                Err(e) => Err(e),
+            }
+        } else {
+            self.buffer.replace(buffer.take());
+            result
+        };
+
+        if let Some(client_buffer) = self.client_buffer.take() {
+            self.client
+                .map(|client| client.write_complete(client_buffer, result));
+        }
+    }
+
+    fn screen_is_ready(&self) {
+        self.client.map(|client| client.screen_is_ready());
+    }
+
+    fn resolution_changed(&self, width: usize, height: usize) {
+        self.client
+            .map(|client| client.resolution_changed(width, height));
+    }
+}
+
+/// Implements `set_brightness()` in software for screens that do not support
+/// it.
+///
+/// Brightness requests are passed to the underlying screen first. If it
+/// returns `NOSUPPORT`, the adapter instead scales the color channels of every
+/// pixel written afterwards by `brightness / 65535`, writing the dimmed pixels
+/// to the underlying screen from the adapter's buffer. Writes that do not fit
+/// in the buffer are written in multiple parts, and the client's buffer is
+/// returned once all parts have been written.
+///
+/// This has some tradeoffs compared to hardware brightness control:
+///
+/// - Only content written after `set_brightness()` is dimmed. Clients must
+///   redraw the screen for a new brightness to take effect everywhere.
+/// - Channels are scaled linearly, so the steps are not uniform in perceived
+///   lightness, and low brightness values lose color resolution.
+/// - Every pixel is copied, which costs CPU time and a buffer.
+///
+/// Software dimming is supported for the RGB_565 (big endian), RGB_888 and
+/// ARGB_8888 pixel formats. The alpha channel is not changed.
+pub struct ScreenSoftwareDim<'a, S: Screen<'a>> {
+    screen: &'a S,
+    client: OptionalCell<&'a dyn ScreenClient>,
+    /// Holds the dimmed pixels.
+    buffer: TakeCell<'static, [u8]>,
+    /// The client's buffer while it is being written.
+    client_buffer: MapCell<SubSliceMut<'static, u8>>,
+    /// Number of bytes of the client's buffer written so far.
+    written: Cell<usize>,
+    /// Brightness applied in software, or `None` if the underlying screen
+    /// controls the brightness.
+    software_brightness: OptionalCell<u16>,
+    deferred_call: DeferredCall,
+}
+
+impl<'a, S: Screen<'a>> ScreenSoftwareDim<'a, S> {
+    pub fn new(screen: &'a S, buffer: &'static mut [u8]) -> Self {
+        Self {
+            screen,
+            client: OptionalCell::empty(),
+            buffer: TakeCell::new(buffer),
+            client_buffer: MapCell::empty(),
+            written: Cell::new(0),
+            software_brightness: OptionalCell::empty(),
+            deferred_call: DeferredCall::new(),
+        }
+    }
+
+    /// The brightness applied by scaling pixels, or `None` if the underlying
+    /// screen controls the brightness.
+    pub fn software_brightness(&self) -> Option<u16> {
+        self.software_brightness.get()
+    }
+
+    /// Bytes per pixel of the underlying screen if it can be dimmed in
+    /// software.
+    fn bytes_per_pixel(&self) -> Option<usize> {
+        match self.screen.get_pixel_format() {
+            ScreenPixelFormat::RGB_565 => Some(2),
+            ScreenPixelFormat::RGB_888 => Some(3),
+            ScreenPixelFormat::ARGB_8888 => Some(4),
+            ScreenPixelFormat::Mono | ScreenPixelFormat::RGB_233 => None,
+        }
+    }
+
+    /// Scale the channel `value` by `brightness`.
+    fn scale(value: u16, brightness: u16) -> u16 {
+        ((value as u32 * brightness as u32) / u16::MAX as u32) as u16
+    }
+
+    /// Dim `pixel` in place.
+    fn dim(pixel: &mut [u8], brightness: u16) {
+        match pixel.len() {
+            2 => {
+                let value = u16::from_be_bytes([pixel[0], pixel[1]]);
+                let red = Self::scale(value >> 11, brightness);
+                let green = Self::scale((value >> 5) & 0x3f, brightness);
+                let blue = Self::scale(value & 0x1f, brightness);
+                pixel.copy_from_slice(&((red << 11) | (green << 5) | blue).to_be_bytes());
+            }
+            // Skip the alpha channel of ARGB_8888 pixels.
+            length => {
+                for channel in &mut pixel[length - 3..] {
+                    *channel = Self::scale(*channel as u16, brightness) as u8;
+                }
+            }
+        }
+    }
+
+    /// Dim and write the next part of the client's buffer.
+    fn write_next(&self, buffer: &'static mut [u8], continue_write: bool) -> Result<(), ErrorCode> {
+        let bytes_per_pixel = self.bytes_per_pixel().ok_or(ErrorCode::NOSUPPORT)?;
+        let brightness = self.software_brightness.unwrap_or(u16::MAX);
+        let written = self.written.get();
+        let length = self.client_buffer.map_or(0, |client_buffer| {
+            let pixels = &client_buffer.as_slice()[written..];
+            let length =
+                core::cmp::min(pixels.len(), buffer.len() - buffer.len() % bytes_per_pixel);
+            buffer[..length].copy_from_slice(&pixels[..length]);
+            for pixel in buffer[..length].chunks_exact_mut(bytes_per_pixel) {
+                Self::dim(pixel, brightness);
+            }
+            length
+        });
+        self.written.set(written + length);
+
+        let mut data = SubSliceMut::new(buffer);
+        data.slice(..length);
+        self.screen.write(data, continue_write)
+    }
+}
+
+impl<'a, S: Screen<'a>> Screen<'a> for ScreenSoftwareDim<'a, S> {
+    fn set_client(&self, client: &'a dyn ScreenClient) {
+        self.client.set(client);
+    }
+
+    fn get_resolution(&self) -> (usize, usize) {
+        self.screen.get_resolution()
+    }
+
+    fn get_pixel_format(&self) -> ScreenPixelFormat {
+        self.screen.get_pixel_format()
+    }
+
+    fn get_rotation(&self) -> ScreenRotation {
+        self.screen.get_rotation()
+    }
+
+    fn set_write_frame(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<(), ErrorCode> {
+        self.screen.set_write_frame(x, y, width, height)
+    }
+
+    fn write(
+        &self,
+        buffer: SubSliceMut<'static, u8>,
+        continue_write: bool,
+    ) -> Result<(), ErrorCode> {
+        if self.client_buffer.is_some() {
+            return Err(ErrorCode::BUSY);
+        }
+        if self.software_brightness.is_none() {
+            return self.screen.write(buffer, continue_write);
+        }
+
+        let bytes_per_pixel = self.bytes_per_pixel().ok_or(ErrorCode::NOSUPPORT)?;
+        if buffer.len() % bytes_per_pixel != 0 {
+            return Err(ErrorCode::SIZE);
+        }
+        let dimmed = self.buffer.take().ok_or(ErrorCode::BUSY)?;
+        if dimmed.len() < bytes_per_pixel {
+            self.buffer.replace(dimmed);
+            return Err(ErrorCode::SIZE);
+        }
+
+        self.client_buffer.replace(buffer);
+        self.written.set(0);
+        self.write_next(dimmed, continue_write).inspect_err(|_| {
+            // The write will not complete, so the client's buffer will not
+            // be returned.
+            self.client_buffer.take();
+        })
+    }
+
+    fn set_brightness(&self, brightness: u16) -> Result<(), ErrorCode> {
+        match self.screen.set_brightness(brightness) {
+            Err(ErrorCode::NOSUPPORT) if self.bytes_per_pixel().is_some() => {
+                self.software_brightness.set(brightness);
+                self.deferred_call.set();
+                Ok(())
+            }
+            result => {
+                if result.is_ok() {
+                    self.software_brightness.clear();
+                }
+                result
+            }
+        }
+    }
+
+    fn set_power(&self, enabled: bool) -> Result<(), ErrorCode> {
+        self.screen.set_power(enabled)
+    }
+
+    fn set_invert(&self, enabled: bool) -> Result<(), ErrorCode> {
+        self.screen.set_invert(enabled)
+    }
+}
+
+impl<'a, S: Screen<'a>> ScreenClient for ScreenSoftwareDim<'a, S> {
+    fn command_complete(&self, result: Result<(), ErrorCode>) {
+        self.client.map(|client| client.command_complete(result));
+    }
+
+    fn write_complete(&self, buffer: SubSliceMut<'static, u8>, result: Result<(), ErrorCode>) {
+        if self.client_buffer.is_none() {
+            // The write was passed through.
+            self.client
+                .map(|client| client.write_complete(buffer, result));
+            return;
+        }
+
+        let remaining = self
+            .client_buffer
+            .map_or(0, |client_buffer| client_buffer.len() - self.written.get());
+
+        let result = if result.is_ok() && remaining > 0 {
+            match self.write_next(buffer.take(), true) {
+                // Wait for the next part to be written.
+                Ok(()) => return,
+                Err(e) => Err(e),
+            }
+        } else {
+            self.buffer.replace(buffer.take());
+            result
+        };
+
+        if let Some(client_buffer) = self.client_buffer.take() {
+            self.client
+                .map(|client| client.write_complete(client_buffer, result));
+        }
+    }
+
+    fn screen_is_ready(&self) {
+        self.client.map(|client| client.screen_is_ready());
+    }
+
+    fn resolution_changed(&self, width: usize, height: usize) {
+        self.client
+            .map(|client| client.resolution_changed(width, height));
+    }
+}
+
+impl<'a, S: Screen<'a>> DeferredCallClient for ScreenSoftwareDim<'a, S> {
+    fn handle_deferred_call(&self) {
+        // Software brightness changes take effect immediately.
+        self.client.map(|client| client.command_complete(Ok(())));
+    }
+
+    fn register(&'static self) {
+        self.deferred_call.register(self);
+    }
+}
+
+/// What `ScreenDoubleBuffer` is doing with the underlying screen.
+#[derive(Clone, Copy, PartialEq)]
+enum PresentState {
+    Idle,
+    /// Waiting for the underlying screen to complete a brightness, power or
+    /// invert command from the client.
+    Command,
+    /// Waiting for the underlying screen to set a full screen write frame.
+    SetFrame,
+    /// Waiting for the underlying screen to write the front buffer.
+    Write,
+}
+
+/// Double buffers a screen to avoid tearing.
+///
+/// Writes to the adapter are stored in a back buffer that holds the whole
+/// screen, and complete without touching the underlying screen. Calling
+/// `present()` copies the back buffer to the front buffer and writes the front
+/// buffer to the underlying screen as a single full screen write, so partial
+/// frames are never visible. Clients can keep drawing into the back buffer
+/// while the front buffer is being written. When the frame has been written,
+/// the client receives a `command_complete()` callback.
+///
+/// Brightness, power and invert commands are passed to the underlying screen
+/// one at a time, and cannot overlap with writing a frame.
+///
+/// Both buffers must be large enough to hold the entire screen, and only pixel
+/// formats that use a whole number of bytes per pixel are supported.
+pub struct ScreenDoubleBuffer<'a, S: Screen<'a>> {
+    screen: &'a S,
+    client: OptionalCell<&'a dyn ScreenClient>,
+    /// The frame being drawn by the client.
+    back: TakeCell<'static, [u8]>,
+    /// The frame being written to the underlying screen.
+    front: TakeCell<'static, [u8]>,
+    /// The client's buffer, returned from the deferred call.
+    client_buffer: MapCell<SubSliceMut<'static, u8>>,
+    /// Whether a `command_complete()` for `set_write_frame()` is pending.
+    frame_pending: Cell<bool>,
+    /// The client's write frame as `(x, y, width, height)`.
+    frame: Cell<(usize, usize, usize, usize)>,
+    /// Number of pixels of the write frame written so far.
+    position: Cell<usize>,
+    state: Cell<PresentState>,
+    deferred_call: DeferredCall,
+}
+
+impl<'a, S: Screen<'a>> ScreenDoubleBuffer<'a, S> {
+    pub fn new(screen: &'a S, back: &'static mut [u8], front: &'static mut [u8]) -> Self {
+        Self {
+            screen,
+            client: OptionalCell::empty(),
+            back: TakeCell::new(back),
+            front: TakeCell::new(front),
+            client_buffer: MapCell::empty(),
+            frame_pending: Cell::new(false),
+            frame: Cell::new((0, 0, 0, 0)),
+            position: Cell::new(0),
+            state: Cell::new(PresentState::Idle),
+            deferred_call: DeferredCall::new(),
+        }
+    }
+
+    /// Number of bytes needed for each of the buffers.
+    pub fn frame_size(&self) -> Option<usize> {
+        let (width, height) = self.screen.get_resolution();
+        self.bytes_per_pixel()
+            .map(|bytes_per_pixel| width * height * bytes_per_pixel)
+    }
+
+    /// Show the current contents of the back buffer on the screen.
+    ///
+    /// Returns `BUSY` if the previous frame is still being written or a
+    /// command from the client is pending on the underlying screen. On
+    /// success, the client receives a `command_complete()` callback once the
+    /// frame has been written to the screen.
+    pub fn present(&self) -> Result<(), ErrorCode> {
+        if self.state.get() != PresentState::Idle {
+            return Err(ErrorCode::BUSY);
+        }
+        let frame_size = self.frame_size().ok_or(ErrorCode::NOSUPPORT)?;
+        let front = self.front.take().ok_or(ErrorCode::BUSY)?;
+        let copied = self.back.map_or(false, |back| {
+            if back.len() < frame_size || front.len() < frame_size {
+                false
+            } else {
+                front[..frame_size].copy_from_slice(&back[..frame_size]);
+                true
+            }
+        });
+        self.front.replace(front);
+        if !copied {
+            return Err(ErrorCode::SIZE);
+        }
+
+        let (width, height) = self.screen.get_resolution();
+        self.screen.set_write_frame(0, 0, width, height)?;
+        self.state.set(PresentState::SetFrame);
+        Ok(())
+    }
+
+    /// Pass a command from the client to the underlying screen, whose
+    /// `command_complete()` is then forwarded to the client.
+    fn command(&self, command: impl FnOnce() -> Result<(), ErrorCode>) -> Result<(), ErrorCode> {
+        if self.state.get() != PresentState::Idle {
+            return Err(ErrorCode::BUSY);
+        }
+        self.state.set(PresentState::Command);
+        command().inspect_err(|_| self.state.set(PresentState::Idle))
+    }
+
+    fn bytes_per_pixel(&self) -> Option<usize> {
+        let bits = self.screen.get_pixel_format().get_bits_per_pixel();
+        if bits % 8 == 0 {
+            Some(bits / 8)
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, S: Screen<'a>> Screen<'a> for ScreenDoubleBuffer<'a, S> {
+    fn set_client(&self, client: &'a dyn ScreenClient) {
+        self.client.set(client);
+    }
+
+    fn get_resolution(&self) -> (usize, usize) {
+        self.screen.get_resolution()
+    }
+
+    fn get_pixel_format(&self) -> ScreenPixelFormat {
+        self.screen.get_pixel_format()
+    }
+
+    fn get_rotation(&self) -> ScreenRotation {
+        self.screen.get_rotation()
+    }
+
+    fn set_write_frame(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<(), ErrorCode> {
+        let (screen_width, screen_height) = self.screen.get_resolution();
+        if x + width > screen_width || y + height > screen_height {
+            return Err(ErrorCode::INVAL);
+        }
+        if self.frame_pending.get() || self.client_buffer.is_some() {
+            return Err(ErrorCode::BUSY);
+        }
+
+        self.frame.set((x, y, width, height));
+        self.position.set(0);
+        self.frame_pending.set(true);
+        self.deferred_call.set();
+        Ok(())
+    }
+
+    fn write(
+        &self,
+        mut buffer: SubSliceMut<'static, u8>,
+        continue_write: bool,
+    ) -> Result<(), ErrorCode> {
+        if self.frame_pending.get() || self.client_buffer.is_some() {
+            return Err(ErrorCode::BUSY);
+        }
+        let bytes_per_pixel = self.bytes_per_pixel().ok_or(ErrorCode::NOSUPPORT)?;
+        let frame_size = self.frame_size().ok_or(ErrorCode::NOSUPPORT)?;
+
+        let (x, y, width, height) = self.frame.get();
+        let position = if continue_write {
+            self.position.get()
+        } else {
+            0
+        };
+        let pixels = buffer.len() / bytes_per_pixel;
+        if buffer.len() % bytes_per_pixel != 0 || position + pixels > width * height {
+            return Err(ErrorCode::SIZE);
+        }
+
+        let (screen_width, _) = self.screen.get_resolution();
+        self.back.map_or(Err(ErrorCode::FAIL), |back| {
+            if back.len() < frame_size {
+                return Err(ErrorCode::SIZE);
+            }
+            for (offset, pixel) in buffer.as_slice().chunks_exact(bytes_per_pixel).enumerate() {
+                let index = position + offset;
+                let target =
+                    ((y + index / width) * screen_width + x + index % width) * bytes_per_pixel;
+                back[target..target + bytes_per_pixel].copy_from_slice(pixel);
+            }
+            Ok(())
+        })?;
+
+        self.position.set(position + pixels);
+        self.client_buffer.replace(buffer);
+        self.deferred_call.set();
+        Ok(())
+    }
+
+    fn set_brightness(&self, brightness: u16) -> Result<(), ErrorCode> {
+        self.command(|| self.screen.set_brightness(brightness))
+    }
+
+    fn set_power(&self, enabled: bool) -> Result<(), ErrorCode> {
+        self.command(|| self.screen.set_power(enabled))
+    }
+
+    fn set_invert(&self, enabled: bool) -> Result<(), ErrorCode> {
+        self.command(|| self.screen.set_invert(enabled))
+    }
+}
+
+impl<'a, S: Screen<'a>> ScreenClient for ScreenDoubleBuffer<'a, S> {
+    fn command_complete(&self, result: Result<(), ErrorCode>) {
+        match self.state.get() {
+            PresentState::Command => {
+                self.state.set(PresentState::Idle);
+                self.client.map(|client| client.command_complete(result));
+                return;
+            }
+            PresentState::SetFrame => {}
+            // No command is pending on the underlying screen.
+            PresentState::Idle | PresentState::Write => return,
+        }
+
+        // The full screen write frame is set, write the front buffer.
+        let result = result.and_then(|()| {
+            let frame_size = self.frame_size().ok_or(ErrorCode::NOSUPPORT)?;
+            let front = self.front.take().ok_or(ErrorCode::FAIL)?;
+            let mut frame = SubSliceMut::new(front);
+            frame.slice(..frame_size);
+            self.screen.write(frame, false)
+        });
+        match result {
+            Ok(()) => self.state.set(PresentState::Write),
+            Err(e) => {
+                self.state.set(PresentState::Idle);
+                self.client.map(|client| client.command_complete(Err(e)));
+            }
+        }
+    }
+
+    fn write_complete(&self, buffer: SubSliceMut<'static, u8>, result: Result<(), ErrorCode>) {
+        // Only `present()` writes to the underlying screen.
+        self.front.replace(buffer.take());
+        self.state.set(PresentState::Idle);
+        self.client.map(|client| client.command_complete(result));
+    }
+
+    fn screen_is_ready(&self) {
+        self.client.map(|client| client.screen_is_ready());
+    }
+
+    fn resolution_changed(&self, width: usize, height: usize) {
+        self.client
+            .map(|client| client.resolution_changed(width, height));
+    }
+}
+
+impl<'a, S: Screen<'a>> DeferredCallClient for ScreenDoubleBuffer<'a, S> {
+    fn handle_deferred_call(&self) {
+        if self.frame_pending.replace(false) {
+            self.client.map(|client| client.command_complete(Ok(())));
+        }
+        if let Some(client_buffer) = self.client_buffer.take() {
+            self.client
+                .map(|client| client.write_complete(client_buffer, Ok(())));
+        }
+    }
+
+    fn register(&'static self) {
+        self.deferred_call.register(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::boxed::Box;
+    use std::vec;
+    use std::vec::Vec;
+
+    const WIDTH: usize = 4;
+    const HEIGHT: usize = 2;
+
+    /// Screen without brightness control that stores written bytes in a
+    /// framebuffer, one byte per RGB_233 pixel.
+    struct MockScreen {
+        format: ScreenPixelFormat,
+        framebuffer: MapCell<Vec<u8>>,
+        frame: Cell<(usize, usize, usize, usize)>,
+        written: MapCell<SubSliceMut<'static, u8>>,
+        client: OptionalCell<&'static dyn ScreenClient>,
+    }
+
+    impl MockScreen {
+        fn pixel(&self, x: usize, y: usize) -> u8 {
+            self.framebuffer.map_or(0, |fb| fb[y * WIDTH + x])
+        }
+
+        fn complete(&self) {
+            if let Some(buffer) = self.written.take() {
+                self.client.map(|c| c.write_complete(buffer, Ok(())));
+            }
+        }
+    }
+
+    impl Screen<'static> for MockScreen {
+        fn set_client(&self, client: &'static dyn ScreenClient) {
+            self.client.set(client);
+        }
+
+        fn get_resolution(&self) -> (usize, usize) {
+            (WIDTH, HEIGHT)
+        }
+
+        fn get_pixel_format(&self) -> ScreenPixelFormat {
+            self.format
+        }
+
+        fn get_rotation(&self) -> ScreenRotation {
+            ScreenRotation::Normal
+        }
+
+        fn set_write_frame(
+            &self,
+            x: usize,
+            y: usize,
+            width: usize,
+            height: usize,
+        ) -> Result<(), ErrorCode> {
+            self.frame.set((x, y, width, height));
+            Ok(())
+        }
+
+        fn write(
+            &self,
+            mut buffer: SubSliceMut<'static, u8>,
+            _continue_write: bool,
+        ) -> Result<(), ErrorCode> {
+            let (x, y, width, _) = self.frame.get();
+            self.framebuffer.map(|fb| {
+                for (i, pixel) in buffer.as_slice().iter().enumerate() {
+                    fb[(y + i / width) * WIDTH + x + i % width] = *pixel;
+                }
+            });
+            self.written.replace(buffer);
+            Ok(())
+        }
+
+        fn set_brightness(&self, _brightness: u16) -> Result<(), ErrorCode> {
+            Err(ErrorCode::NOSUPPORT)
+        }
+
+        fn set_power(&self, _enabled: bool) -> Result<(), ErrorCode> {
+            Ok(())
+        }
+
+        fn set_invert(&self, _enabled: bool) -> Result<(), ErrorCode> {
+            Ok(())
+        }
+    }
+
+    struct Client {
+        completed: Cell<usize>,
+        commands: Cell<usize>,
+        resolution: Cell<(usize, usize)>,
+    }
+
+    impl ScreenClient for Client {
+        fn command_complete(&self, result: Result<(), ErrorCode>) {
+            assert_eq!(result, Ok(()));
+            self.commands.set(self.commands.get() + 1);
+        }
+
+        fn write_complete(&self, _buffer: SubSliceMut<'static, u8>, result: Result<(), ErrorCode>) {
+            assert_eq!(result, Ok(()));
+            self.completed.set(self.completed.get() + 1);
+        }
+
+        fn screen_is_ready(&self) {}
+
+        fn resolution_changed(&self, width: usize, height: usize) {
+            self.resolution.set((width, height));
+        }
+    }
+
+    fn mock_screen(format: ScreenPixelFormat) -> &'static MockScreen {
+        Box::leak(Box::new(MockScreen {
+            format,
+            framebuffer: MapCell::new(vec![0; WIDTH * HEIGHT]),
+            frame: Cell::new((0, 0, 0, 0)),
+            written: MapCell::empty(),
+            client: OptionalCell::empty(),
+        }))
+    }
+
+    fn client() -> &'static Client {
+        Box::leak(Box::new(Client {
+            completed: Cell::new(0),
+            commands: Cell::new(0),
+            resolution: Cell::new((0, 0)),
+        }))
+    }
+
+    fn setup(
+        rotation: ScreenRotation,
+    ) -> (
+        &'static MockScreen,
+        &'static ScreenRotate<'static, MockScreen>,
+        &'static Client,
+    ) {
+        let screen = mock_screen(ScreenPixelFormat::RGB_233);
+        let buffer: &'static mut [u8] = Box::leak(Box::new([0; WIDTH * HEIGHT]));
+        let rotate: &'static ScreenRotate<'static, MockScreen> =
+            Box::leak(Box::new(ScreenRotate::new(screen, rotation, buffer)));
+        let client = client();
+        screen.set_client(rotate);
+        rotate.set_client(client);
+        (screen, rotate, client)
+    }
+
+    fn pixels(data: &[u8]) -> SubSliceMut<'static, u8> {
+        SubSliceMut::new(Box::leak(data.to_vec().into_boxed_slice()))
+    }
+
+    #[test]
+    fn rotated_90_write_is_transposed() {
+        let (screen, rotate, client) = setup(ScreenRotation::Rotated90);
+        assert_eq!(rotate.get_resolution(), (HEIGHT, WIDTH));
+        assert!(ScreenRotation::Rotated90 == rotate.get_rotation());
+
+        // A 2x1 frame at the top left of the rotated screen is a 1x2 frame
+        // at the bottom left of the screen.
+        assert_eq!(rotate.set_write_frame(0, 0, 2, 1), Ok(()));
+        assert_eq!(screen.frame.get(), (0, 0, 1, 2));
+        assert_eq!(rotate.write(pixels(&[1, 2]), false), Ok(()));
+        screen.complete();
+        assert_eq!(client.completed.get(), 1);
+        assert_eq!(screen.pixel(0, 1), 1);
+        assert_eq!(screen.pixel(0, 0), 2);
+
+        // The bottom right of the rotated screen is the top right of the
+        // screen.
+        assert_eq!(rotate.set_write_frame(1, 3, 1, 1), Ok(()));
+        assert_eq!(rotate.write(pixels(&[3]), false), Ok(()));
+        screen.complete();
+        assert_eq!(screen.pixel(3, 0), 3);
+    }
+
+    #[test]
+    fn partial_writes_complete_the_frame() {
+        let (screen, rotate, client) = setup(ScreenRotation::Rotated180);
+        assert_eq!(rotate.set_write_frame(0, 0, WIDTH, HEIGHT), Ok(()));
+        assert_eq!(rotate.write(pixels(&[1, 2, 3, 4]), false), Ok(()));
+        rotate.handle_deferred_call();
+        assert_eq!(client.completed.get(), 1);
+        assert_eq!(screen.pixel(0, 0), 0);
+
+        assert_eq!(rotate.write(pixels(&[5, 6, 7, 8]), true), Ok(()));
+        screen.complete();
+        assert_eq!(client.completed.get(), 2);
+        assert_eq!(screen.pixel(3, 1), 1);
+        assert_eq!(screen.pixel(0, 1), 4);
+        assert_eq!(screen.pixel(0, 0), 8);
+
+        assert_eq!(rotate.write(pixels(&[0; 9]), false), Err(ErrorCode::SIZE));
+    }
+
+    #[test]
+    fn software_dimming_scales_channels() {
+        let screen = mock_screen(ScreenPixelFormat::RGB_888);
+        // Room for one pixel, so each pixel is written separately.
+        let buffer: &'static mut [u8] = Box::leak(Box::new([0; 4]));
+        let dim: &'static ScreenSoftwareDim<'static, MockScreen> =
+            Box::leak(Box::new(ScreenSoftwareDim::new(screen, buffer)));
+        let client = client();
+        screen.set_client(dim);
+        dim.set_client(client);
+
+        // Full brightness until set otherwise, and writes pass through.
+        assert_eq!(dim.software_brightness(), None);
+        assert_eq!(dim.set_write_frame(0, 0, WIDTH, HEIGHT), Ok(()));
+        assert_eq!(dim.write(pixels(&[0xff, 0x80, 0x00]), false), Ok(()));
+        screen.complete();
+        assert_eq!(client.completed.get(), 1);
+        assert_eq!(screen.pixel(1, 0), 0x80);
+
+        // The mock screen has no brightness control, so dim in software.
+        assert_eq!(dim.set_brightness(u16::MAX / 2), Ok(()));
+        dim.handle_deferred_call();
+        assert_eq!(client.commands.get(), 1);
+        assert_eq!(dim.software_brightness(), Some(u16::MAX / 2));
+
+        // The mock screen writes every part at the start of the frame, so
+        // check each pixel after it is written.
+        let data = pixels(&[0xff, 0x80, 0x00, 0x10, 0x20, 0x30]);
+        assert_eq!(dim.write(data, false), Ok(()));
+        assert_eq!(
+            screen.framebuffer.map(|fb| fb[..3].to_vec()),
+            Some(vec![0x7f, 0x3f, 0x00])
+        );
+        screen.complete();
+        assert_eq!(client.completed.get(), 1);
+        assert_eq!(
+            screen.framebuffer.map(|fb| fb[..3].to_vec()),
+            Some(vec![0x07, 0x0f, 0x17])
+        );
+        screen.complete();
+        assert_eq!(client.completed.get(), 2);
+
+        // Writes must be whole pixels.
+        assert_eq!(dim.write(pixels(&[0; 4]), false), Err(ErrorCode::SIZE));
+    }
+
+    #[test]
+    fn argb8888_is_converted_to_rgb565() {
+        let screen = mock_screen(ScreenPixelFormat::RGB_565);
+        // Room for two converted pixels, so three pixels take two parts.
+        let buffer: &'static mut [u8] = Box::leak(Box::new([0; 4]));
+        let convert: &'static ScreenARGB8888ToRGB565<'static, MockScreen> =
+            Box::leak(Box::new(ScreenARGB8888ToRGB565::new(screen, buffer)));
+        let client = client();
+        screen.set_client(convert);
+        convert.set_client(client);
+        assert!(convert.get_pixel_format() == ScreenPixelFormat::ARGB_8888);
+
+        // The mock screen stores one byte per pixel, so a frame of the whole
+        // screen stores the converted bytes in order.
+        assert_eq!(convert.set_write_frame(0, 0, WIDTH, HEIGHT), Ok(()));
+        let data = pixels(&[
+            0xff, 0xff, 0x00, 0x00, // red
+            0x00, 0x00, 0xff, 0x00, // green
+            0x00, 0x00, 0x00, 0xff, // blue
+        ]);
+        assert_eq!(convert.write(data, false), Ok(()));
+        assert_eq!(
+            screen.framebuffer.map(|fb| fb[..4].to_vec()),
+            Some(vec![0xf8, 0x00, 0x07, 0xe0])
+        );
+        screen.complete();
+        assert_eq!(client.completed.get(), 0);
+        assert_eq!(
+            screen.framebuffer.map(|fb| fb[..2].to_vec()),
+            Some(vec![0x00, 0x1f])
+        );
+        screen.complete();
+        assert_eq!(client.completed.get(), 1);
+
+        // Writes must be whole pixels.
+        assert_eq!(convert.write(pixels(&[0; 3]), false), Err(ErrorCode::SIZE));
+    }
+
+    #[test]
+    fn argb8888_conversion_requires_rgb565_screen() {
+        let screen = mock_screen(ScreenPixelFormat::RGB_888);
+        let buffer: &'static mut [u8] = Box::leak(Box::new([0; 4]));
+        let convert: &'static ScreenARGB8888ToRGB565<'static, MockScreen> =
+            Box::leak(Box::new(ScreenARGB8888ToRGB565::new(screen, buffer)));
+        assert_eq!(
+            convert.write(pixels(&[0; 4]), false),
+            Err(ErrorCode::NOSUPPORT)
+        );
+        assert!(screen.written.is_none());
+    }
+
+    #[test]
+    fn double_buffer_shows_writes_on_present() {
+        let screen = mock_screen(ScreenPixelFormat::RGB_233);
+        let back: &'static mut [u8] = Box::leak(Box::new([0; WIDTH * HEIGHT]));
+        let front: &'static mut [u8] = Box::leak(Box::new([0; WIDTH * HEIGHT]));
+        let double: &'static ScreenDoubleBuffer<'static, MockScreen> =
+            Box::leak(Box::new(ScreenDoubleBuffer::new(screen, back, front)));
+        let client = client();
+        screen.set_client(double);
+        double.set_client(client);
+
+        assert_eq!(double.set_write_frame(1, 0, 2, 2), Ok(()));
+        double.handle_deferred_call();
+        assert_eq!(client.commands.get(), 1);
+
+        // Writes complete without reaching the screen.
+        assert_eq!(double.write(pixels(&[1, 2]), false), Ok(()));
+        double.handle_deferred_call();
+        assert_eq!(double.write(pixels(&[3, 4]), true), Ok(()));
+        double.handle_deferred_call();
+        assert_eq!(client.completed.get(), 2);
+        assert!(screen.written.is_none());
+        assert_eq!(screen.pixel(1, 0), 0);
+
+        // Presenting writes the whole frame at once.
+        assert_eq!(double.present(), Ok(()));
+        assert_eq!(double.present(), Err(ErrorCode::BUSY));
+        assert_eq!(screen.frame.get(), (0, 0, WIDTH, HEIGHT));
+        double.command_complete(Ok(()));
+        assert_eq!(screen.pixel(1, 0), 1);
+        assert_eq!(screen.pixel(2, 0), 2);
+        assert_eq!(screen.pixel(1, 1), 3);
+        assert_eq!(screen.pixel(2, 1), 4);
+        assert_eq!(screen.pixel(0, 0), 0);
+
+        // Keep drawing while the frame is written.
+        assert_eq!(double.set_write_frame(0, 0, 1, 1), Ok(()));
+        double.handle_deferred_call();
+        assert_eq!(double.write(pixels(&[5]), false), Ok(()));
+        double.handle_deferred_call();
+        assert_eq!(screen.pixel(0, 0), 0);
+
+        screen.complete();
+        assert_eq!(client.commands.get(), 3);
+        assert_eq!(double.present(), Ok(()));
+        double.command_complete(Ok(()));
+        assert_eq!(screen.pixel(0, 0), 5);
+        assert_eq!(screen.pixel(1, 0), 1);
+    }
+
+    #[test]
+    fn double_buffer_forwards_only_client_commands() {
+        let screen = mock_screen(ScreenPixelFormat::RGB_233);
+        let back: &'static mut [u8] = Box::leak(Box::new([0; WIDTH * HEIGHT]));
+        let front: &'static mut [u8] = Box::leak(Box::new([0; WIDTH * HEIGHT]));
+        let double: &'static ScreenDoubleBuffer<'static, MockScreen> =
+            Box::leak(Box::new(ScreenDoubleBuffer::new(screen, back, front)));
+        let client = client();
+        screen.set_client(double);
+        double.set_client(client);
+
+        // A command from the client is completed by the screen, and blocks
+        // presenting until then.
+        assert_eq!(double.set_power(true), Ok(()));
+        assert_eq!(double.present(), Err(ErrorCode::BUSY));
+        assert_eq!(double.set_invert(true), Err(ErrorCode::BUSY));
+        double.command_complete(Ok(()));
+        assert_eq!(client.commands.get(), 1);
+
+        // Failed commands are not pending.
+        assert_eq!(double.set_brightness(1), Err(ErrorCode::NOSUPPORT));
+
+        // Setting the full screen frame for `present()` is not reported, and
+        // commands cannot start until the frame is written.
+        assert_eq!(double.present(), Ok(()));
+        double.command_complete(Ok(()));
+        assert_eq!(client.commands.get(), 1);
+        assert_eq!(double.set_power(false), Err(ErrorCode::BUSY));
+        double.command_complete(Ok(()));
+        assert_eq!(client.commands.get(), 1);
+        screen.complete();
+        assert_eq!(client.commands.get(), 2);
+        assert_eq!(double.set_power(false), Ok(()));
+    }
+
+    #[test]
+    fn rotated_resolution_change_is_swapped() {
+        let (_screen, rotate, client) = setup(ScreenRotation::Rotated90);
+        rotate.resolution_changed(8, 4);
+        assert_eq!(client.resolution.get(), (4, 8));
+
+        let (_screen, rotate, client) = setup(ScreenRotation::Rotated180);
+        rotate.resolution_changed(8, 4);
+        assert_eq!(client.resolution.get(), (8, 4));
+    }
+}
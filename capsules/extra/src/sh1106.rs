@@ -10,6 +10,11 @@
 //!   setting frames on the screen.
 //! - The driver does not automatically wrap to the next page. This driver
 //!   manually sets up each page (row).
+//!
+//! The SH1106 RAM is 132 columns wide while common panels are 128 pixels wide,
+//! so the visible area starts at a panel-specific column offset (usually 2,
+//! see `DEFAULT_COLUMN_OFFSET`). If the offset is wrong the image is shifted
+//! horizontally and wraps around, leaving a seam at the edge of the screen.
 
 use core::cell::Cell;
 
@@ -25,6 +30,9 @@ pub const BUFFER_SIZE: usize = 132;
 const WIDTH: usize = 128;
 const HEIGHT: usize = 64;
 
+/// Column of the SH1106 RAM where most 128 pixel wide panels start.
+pub const DEFAULT_COLUMN_OFFSET: u8 = 2;
+
 // #[derive(Copy, Clone, PartialEq)]
 #[derive(Clone, Copy, PartialEq)]
 enum State {
@@ -43,6 +51,8 @@ pub struct Sh1106<'a, I: hil::i2c::I2CDevice> {
     buffer: TakeCell<'static, [u8]>,
     write_buffer: MapCell<SubSliceMut<'static, u8>>,
     enable_charge_pump: bool,
+    /// First RAM column displayed by the panel.
+    column_offset: u8,
 
     active_frame_x: Cell<u8>,
     active_frame_y: Cell<u8>,
@@ -51,7 +61,12 @@ pub struct Sh1106<'a, I: hil::i2c::I2CDevice> {
 }
 
 impl<'a, I: hil::i2c::I2CDevice> Sh1106<'a, I> {
-    pub fn new(i2c: &'a I, buffer: &'static mut [u8], enable_charge_pump: bool) -> Self {
+    pub fn new(
+        i2c: &'a I,
+        buffer: &'static mut [u8],
+        enable_charge_pump: bool,
+        column_offset: u8,
+    ) -> Self {
         Self {
             i2c,
             state: Cell::new(State::Idle),
@@ -60,6 +75,7 @@ impl<'a, I: hil::i2c::I2CDevice> Sh1106<'a, I> {
             buffer: TakeCell::new(buffer),
             write_buffer: MapCell::empty(),
             enable_charge_pump,
+            column_offset,
             active_frame_x: Cell::new(0),
             active_frame_y: Cell::new(0),
             active_frame_width: Cell::new(0),
@@ -209,7 +225,7 @@ impl<'a, I: hil::i2c::I2CDevice> Sh1106<'a, I> {
     }
 
     fn set_page(&self, page_index: u8) -> Result<(), ErrorCode> {
-        let column_start = self.active_frame_x.get() + 2;
+        let column_start = self.active_frame_x.get() + self.column_offset;
         let commands = [
             Command::SetPageStartAddress {
                 address: page_index,
@@ -302,8 +318,8 @@ impl<'a, I: hil::i2c::I2CDevice> hil::screen::Screen<'a> for Sh1106<'a, I> {
         self.active_frame_height.set(height as u8);
 
         // The driver RAM is 132 bytes wide, the screen is 128 bytes wide, so we
-        // offset by two.
-        let column_start: u8 = (x as u8) + 2;
+        // offset by the panel's column offset.
+        let column_start: u8 = (x as u8) + self.column_offset;
         let commands = [
             Command::SetPageStartAddress {
                 address: (y / 8) as u8,
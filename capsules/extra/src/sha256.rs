@@ -22,6 +22,8 @@ use kernel::utilities::leasable_buffer::SubSliceMut;
 use kernel::utilities::leasable_buffer::SubSliceMutImmut;
 use kernel::ErrorCode;
 
+use crate::digest_client::DigestClient;
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum State {
     Idle,
@@ -51,7 +53,7 @@ const ROUND_CONSTANTS: [u32; NUM_ROUND_CONSTANTS] = [
 pub struct Sha256Software<'a> {
     state: Cell<State>,
 
-    client: OptionalCell<&'a dyn Client<SHA_256_OUTPUT_LEN_BYTES>>,
+    client: OptionalCell<DigestClient<'a, SHA_256_OUTPUT_LEN_BYTES>>,
     input_data: OptionalCell<SubSliceMutImmut<'static, u8>>,
     data_buffer: MapCell<[u8; SHA_BLOCK_LEN_BYTES]>,
     buffered_length: Cell<usize>,
@@ -260,7 +262,10 @@ impl<'a> Sha256Software<'a> {
             let mut s1 = self.right_rotate(message_schedule[i - 2], 17);
             s1 ^= self.right_rotate(message_schedule[i - 2], 19);
             s1 ^= message_schedule[i - 2] >> 10;
-            message_schedule[i] = message_schedule[i - 16] + s0 + message_schedule[i - 7] + s1;
+            message_schedule[i] = message_schedule[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(message_schedule[i - 7])
+                .wrapping_add(s1);
         }
 
         // Compression
@@ -271,12 +276,16 @@ impl<'a> Sha256Software<'a> {
                 ^ self.right_rotate(hashes[4], 25);
             let ch = (hashes[4] & hashes[5]) ^ ((!hashes[4]) & hashes[6]);
             let constant = ROUND_CONSTANTS[i];
-            let temp1 = hashes[7] + s1 + ch + constant + message_schedule[i];
+            let temp1 = hashes[7]
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(constant)
+                .wrapping_add(message_schedule[i]);
             let s0 = self.right_rotate(hashes[0], 2)
                 ^ self.right_rotate(hashes[0], 13)
                 ^ self.right_rotate(hashes[0], 22);
             let maj = (hashes[0] & hashes[1]) ^ (hashes[0] & hashes[2]) ^ (hashes[1] & hashes[2]);
-            let temp2 = s0 + maj;
+            let temp2 = s0.wrapping_add(maj);
 
             hashes[7] = hashes[6];
             hashes[6] = hashes[5];
@@ -331,8 +340,8 @@ impl<'a> DigestData<'a, 32> for Sha256Software<'a> {
         self.initialize();
     }
 
-    fn set_data_client(&'a self, _client: &'a (dyn ClientData<32> + 'a)) {
-        unimplemented!()
+    fn set_data_client(&'a self, client: &'a (dyn ClientData<32> + 'a)) {
+        self.client.set(DigestClient::Data(client));
     }
 }
 
@@ -359,8 +368,8 @@ impl<'a> DigestHash<'a, 32> for Sha256Software<'a> {
         }
     }
 
-    fn set_hash_client(&'a self, _client: &'a (dyn ClientHash<32> + 'a)) {
-        unimplemented!()
+    fn set_hash_client(&'a self, client: &'a (dyn ClientHash<32> + 'a)) {
+        self.client.set(DigestClient::Hash(client));
     }
 }
 
@@ -380,14 +389,14 @@ impl<'a> DigestVerify<'a, 32> for Sha256Software<'a> {
         }
     }
 
-    fn set_verify_client(&'a self, _client: &'a (dyn ClientVerify<32> + 'a)) {
-        unimplemented!()
+    fn set_verify_client(&'a self, client: &'a (dyn ClientVerify<32> + 'a)) {
+        self.client.set(DigestClient::Verify(client));
     }
 }
 
 impl<'a> Digest<'a, 32> for Sha256Software<'a> {
     fn set_client(&'a self, client: &'a dyn Client<32>) {
-        self.client.set(client);
+        self.client.set(DigestClient::Digest(client));
     }
 }
 
@@ -494,13 +503,13 @@ impl Sha256 for Sha256Software<'_> {
 }
 
 impl<'a> DigestDataHash<'a, 32> for Sha256Software<'a> {
-    fn set_client(&'a self, _client: &'a dyn ClientDataHash<32>) {
-        unimplemented!()
+    fn set_client(&'a self, client: &'a dyn ClientDataHash<32>) {
+        self.client.set(DigestClient::DataHash(client));
     }
 }
 
 impl<'a> DigestDataVerify<'a, 32> for Sha256Software<'a> {
-    fn set_client(&'a self, _client: &'a dyn ClientDataVerify<32>) {
-        unimplemented!()
+    fn set_client(&'a self, client: &'a dyn ClientDataVerify<32>) {
+        self.client.set(DigestClient::DataVerify(client));
     }
 }
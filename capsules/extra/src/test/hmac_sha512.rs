@@ -0,0 +1,127 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright Tock Contributors 2024.
+
+//! Test the software implementation of HMAC-SHA512 by performing a hash and
+//! checking it against the expected hash value.
+
+use crate::hmac_sha512::HmacSha512Software;
+use crate::sha512::Sha512Software;
+use capsules_core::test::capsule_test::{CapsuleTest, CapsuleTestClient, CapsuleTestError};
+use kernel::hil::digest;
+use kernel::hil::digest::HmacSha512;
+use kernel::hil::digest::{DigestData, DigestHash};
+use kernel::utilities::cells::OptionalCell;
+use kernel::utilities::cells::TakeCell;
+use kernel::utilities::leasable_buffer::SubSlice;
+use kernel::utilities::leasable_buffer::SubSliceMut;
+use kernel::ErrorCode;
+
+pub struct TestHmacSha512 {
+    hmac: &'static HmacSha512Software<'static, Sha512Software<'static>>,
+    key: TakeCell<'static, [u8]>,        // The key to use for HMAC
+    data: TakeCell<'static, [u8]>,       // The data to hash
+    digest: TakeCell<'static, [u8; 64]>, // The supplied hash
+    correct: &'static [u8; 64],          // The supplied hash
+    client: OptionalCell<&'static dyn CapsuleTestClient>,
+}
+
+impl TestHmacSha512 {
+    pub fn new(
+        hmac: &'static HmacSha512Software<'static, Sha512Software<'static>>,
+        key: &'static mut [u8],
+        data: &'static mut [u8],
+        digest: &'static mut [u8; 64],
+        correct: &'static [u8; 64],
+    ) -> Self {
+        TestHmacSha512 {
+            hmac,
+            key: TakeCell::new(key),
+            data: TakeCell::new(data),
+            digest: TakeCell::new(digest),
+            correct,
+            client: OptionalCell::empty(),
+        }
+    }
+
+    pub fn run(&'static self) {
+        kernel::hil::digest::Digest::set_client(self.hmac, self);
+
+        let key = self.key.take().unwrap();
+        let r = self.hmac.set_mode_hmacsha512(key);
+        if r.is_err() {
+            panic!("HmacSha512Test: failed to set key: {:?}", r);
+        }
+        let data = self.data.take().unwrap();
+        let buffer = SubSliceMut::new(data);
+        let r = self.hmac.add_mut_data(buffer);
+        if r.is_err() {
+            panic!("HmacSha512Test: failed to add data: {:?}", r);
+        }
+    }
+}
+
+impl digest::ClientData<64> for TestHmacSha512 {
+    fn add_data_done(&self, _result: Result<(), ErrorCode>, _data: SubSlice<'static, u8>) {}
+
+    fn add_mut_data_done(&self, result: Result<(), ErrorCode>, data: SubSliceMut<'static, u8>) {
+        self.data.replace(data.take());
+
+        match result {
+            Ok(()) => {}
+            Err(e) => {
+                kernel::debug!("HmacSha512Test: failed to add data: {:?}", e);
+                self.client.map(|client| {
+                    client.done(Err(CapsuleTestError::ErrorCode(e)));
+                });
+                return;
+            }
+        }
+
+        let r = self.hmac.run(self.digest.take().unwrap());
+        match r {
+            Ok(()) => {}
+            Err((e, d)) => {
+                kernel::debug!("HmacSha512Test: failed to run HMAC: {:?}", e);
+
+                self.digest.replace(d);
+                self.client.map(|client| {
+                    client.done(Err(CapsuleTestError::ErrorCode(e)));
+                });
+            }
+        }
+    }
+}
+
+impl digest::ClientHash<64> for TestHmacSha512 {
+    fn hash_done(&self, _result: Result<(), ErrorCode>, digest: &'static mut [u8; 64]) {
+        let mut error = false;
+        for i in 0..64 {
+            if self.correct[i] != digest[i] {
+                error = true;
+            }
+        }
+        if !error {
+            kernel::debug!("HMAC-SHA512 matches!");
+            self.client.map(|client| {
+                client.done(Ok(()));
+            });
+        } else {
+            kernel::debug!("HmacSha512Test: incorrect HMAC output!");
+            self.client.map(|client| {
+                client.done(Err(CapsuleTestError::IncorrectResult));
+            });
+        }
+    }
+}
+
+impl digest::ClientVerify<64> for TestHmacSha512 {
+    fn verification_done(&self, _result: Result<bool, ErrorCode>, _compare: &'static mut [u8; 64]) {
+    }
+}
+
+impl CapsuleTest for TestHmacSha512 {
+    fn set_client(&self, client: &'static dyn CapsuleTestClient) {
+        self.client.set(client);
+    }
+}
@@ -7,7 +7,9 @@ pub mod aes_ccm;
 pub mod aes_gcm;
 pub mod crc;
 pub mod hmac_sha256;
+pub mod hmac_sha512;
 pub mod kv_system;
 pub mod sha256;
+pub mod sha512;
 pub mod siphash24;
 pub mod udp;
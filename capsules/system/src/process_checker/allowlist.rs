@@ -0,0 +1,196 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright Tock Contributors 2024.
+
+//! Credential checking policy based only on the credential type.
+//!
+//! `AppCheckerTypeAllowlist` accepts credentials whose format is in a list of
+//! allowed types and rejects credentials whose format is in a list of
+//! disallowed types. Credentials of any other type are passed on. It does no
+//! cryptography: it does not check that a credential is valid, only what kind
+//! of credential it is. This is useful for boards that want to make sure that,
+//! for example, processes with `Reserved` credentials never run.
+//!
+//! ### Usage
+//!
+//! ```rust,ignore
+//! let checker = static_init!(
+//!     AppCheckerTypeAllowlist<'static>,
+//!     AppCheckerTypeAllowlist::new(
+//!         &[TbfFooterV2CredentialsType::SHA256],
+//!         &[TbfFooterV2CredentialsType::Reserved],
+//!         true,
+//!     )
+//! );
+//! checker.register();
+//! ```
+
+use kernel::deferred_call::{DeferredCall, DeferredCallClient};
+use kernel::process_checker::CheckResult;
+use kernel::process_checker::{AppCredentialsPolicy, AppCredentialsPolicyClient};
+use kernel::utilities::cells::OptionalCell;
+use kernel::ErrorCode;
+use tock_tbf::types::TbfFooterV2Credentials;
+use tock_tbf::types::TbfFooterV2CredentialsType;
+
+/// Credential checking policy that accepts or rejects credentials based on
+/// their format.
+pub struct AppCheckerTypeAllowlist<'a> {
+    allowed: &'a [TbfFooterV2CredentialsType],
+    disallowed: &'a [TbfFooterV2CredentialsType],
+    require_credentials: bool,
+    deferred_call: DeferredCall,
+    client: OptionalCell<&'a dyn AppCredentialsPolicyClient<'a>>,
+    credentials: OptionalCell<TbfFooterV2Credentials>,
+    binary: OptionalCell<&'a [u8]>,
+}
+
+impl<'a> AppCheckerTypeAllowlist<'a> {
+    /// Create a checker that accepts credentials whose format is in
+    /// `allowed`, and rejects credentials whose format is in `disallowed`.
+    ///
+    /// If a format is in both lists it is rejected. If `require_credentials`
+    /// is `true`, processes without any accepted credential do not run.
+    pub fn new(
+        allowed: &'a [TbfFooterV2CredentialsType],
+        disallowed: &'a [TbfFooterV2CredentialsType],
+        require_credentials: bool,
+    ) -> Self {
+        Self {
+            allowed,
+            disallowed,
+            require_credentials,
+            deferred_call: DeferredCall::new(),
+            client: OptionalCell::empty(),
+            credentials: OptionalCell::empty(),
+            binary: OptionalCell::empty(),
+        }
+    }
+
+    fn check_format(&self, format: TbfFooterV2CredentialsType) -> CheckResult {
+        if self.disallowed.contains(&format) {
+            CheckResult::Reject
+        } else if self.allowed.contains(&format) {
+            CheckResult::Accept
+        } else {
+            CheckResult::Pass
+        }
+    }
+}
+
+impl<'a> DeferredCallClient for AppCheckerTypeAllowlist<'a> {
+    fn handle_deferred_call(&self) {
+        if let (Some(credentials), Some(binary)) = (self.credentials.take(), self.binary.take()) {
+            let result = self.check_format(credentials.format());
+            self.client
+                .map(|client| client.check_done(Ok(result), credentials, binary));
+        }
+    }
+
+    fn register(&'static self) {
+        self.deferred_call.register(self);
+    }
+}
+
+impl<'a> AppCredentialsPolicy<'a> for AppCheckerTypeAllowlist<'a> {
+    fn require_credentials(&self) -> bool {
+        self.require_credentials
+    }
+
+    fn check_credentials(
+        &self,
+        credentials: TbfFooterV2Credentials,
+        binary: &'a [u8],
+    ) -> Result<(), (ErrorCode, TbfFooterV2Credentials, &'a [u8])> {
+        if self.credentials.is_some() {
+            return Err((ErrorCode::BUSY, credentials, binary));
+        }
+
+        self.credentials.set(credentials);
+        self.binary.set(binary);
+        self.deferred_call.set();
+        Ok(())
+    }
+
+    fn set_client(&self, client: &'a dyn AppCredentialsPolicyClient<'a>) {
+        self.client.replace(client);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process_checker::test_utils::{concat, credentials, footer, leak};
+    use crate::process_checker::test_utils::{start_check, Outcome, RecordingClient};
+
+    static RESERVED: [u8; 68] = credentials(TbfFooterV2CredentialsType::Reserved, 0xa5);
+    static SHA256: [u8; 68] = credentials(TbfFooterV2CredentialsType::SHA256, 0xa5);
+    static SHA384: [u8; 68] = credentials(TbfFooterV2CredentialsType::SHA384, 0xa5);
+    static SHA512: [u8; 68] = credentials(TbfFooterV2CredentialsType::SHA512, 0xa5);
+    static BINARY: [u8; 16] = [0; 16];
+
+    #[test]
+    fn mixed_footer_types() {
+        let checker = leak(AppCheckerTypeAllowlist::new(
+            &[
+                TbfFooterV2CredentialsType::SHA256,
+                TbfFooterV2CredentialsType::SHA512,
+            ],
+            &[
+                TbfFooterV2CredentialsType::Reserved,
+                TbfFooterV2CredentialsType::SHA512,
+            ],
+            true,
+        ));
+        let client = RecordingClient::new();
+        checker.set_client(client);
+
+        for (footer, expected) in [
+            (&SHA256, CheckResult::Accept),
+            (&SHA384, CheckResult::Pass),
+            (&RESERVED, CheckResult::Reject),
+            // In both lists, so rejected.
+            (&SHA512, CheckResult::Reject),
+        ] {
+            let credentials = TbfFooterV2Credentials::try_from(&footer[..]).unwrap();
+            assert!(checker.check_credentials(credentials, &BINARY).is_ok());
+            // Only one check can be in progress at a time.
+            assert!(checker.check_credentials(credentials, &BINARY).is_err());
+            checker.handle_deferred_call();
+            assert_eq!(client.result.take(), Some(Ok(expected)));
+        }
+    }
+
+    /// Check a binary with two `footers` with a checker that allows SHA256
+    /// and disallows Reserved credentials.
+    fn check_two_footers(footers: &'static [u8]) -> Option<Outcome> {
+        let checker = leak(AppCheckerTypeAllowlist::new(
+            &[TbfFooterV2CredentialsType::SHA256],
+            &[TbfFooterV2CredentialsType::Reserved],
+            true,
+        ));
+        let client = start_check(checker, footers);
+        // Each footer is checked in a deferred call.
+        for _ in 0..2 {
+            checker.handle_deferred_call();
+        }
+        client.outcome.get()
+    }
+
+    #[test]
+    fn unlisted_footer_is_skipped() {
+        static SHA384_THEN_SHA256: [u8; 96] = concat(&[
+            &footer::<56>(TbfFooterV2CredentialsType::SHA384),
+            &footer::<40>(TbfFooterV2CredentialsType::SHA256),
+        ]);
+        static SHA384_THEN_RESERVED: [u8; 64] = concat(&[
+            &footer::<56>(TbfFooterV2CredentialsType::SHA384),
+            &footer::<8>(TbfFooterV2CredentialsType::Reserved),
+        ]);
+
+        // The SHA384 footer is neither allowed nor disallowed, so the
+        // decision is made by the footer after it.
+        assert_eq!(check_two_footers(&SHA384_THEN_SHA256), Some(Ok(())));
+        assert_eq!(check_two_footers(&SHA384_THEN_RESERVED), Some(Err(Some(1))));
+    }
+}
@@ -199,7 +199,16 @@ impl ClientHash<32_usize> for AppCheckerSha256 {
 /// A sample AppID Assignment tool that assigns pseudo-unique AppIDs and
 /// ShortIds based on the process name.
 ///
-/// ShortIds are assigned as a non-secure hash of the process name.
+/// ShortIds are assigned as a non-secure hash of the process name. A process
+/// therefore keeps its ShortId when it is reflashed, as long as its name does
+/// not change, so state keyed on the ShortId (e.g. storage permissions)
+/// persists across updates.
+///
+/// Two processes with different names whose names hash to the same value get
+/// the same ShortId, and so cannot run at the same time. With a 32 bit hash
+/// such as CRC32 this is unlikely for the handful of processes on a board, but
+/// boards should check the ShortIds of the processes they ship. A name that
+/// hashes to zero receives `ShortId::LocallyUnique`.
 ///
 /// ### Usage
 ///
@@ -329,3 +338,90 @@ impl<'a> AppCredentialsPolicy<'a> for AppCheckerRsaSimulated<'a> {
         self.client.replace(client);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process_checker::test_utils::{self, fold, leak, tbf_header};
+    use crate::process_checker::test_utils::{MockDigest, RecordingClient};
+    use kernel::hil::digest::DigestDataVerify;
+    use kernel::utilities::helpers::crc32_posix;
+
+    static HOTP: [u8; 40] = tbf_header(b"hotp");
+    // The same app flashed at a different location.
+    static HOTP_REFLASHED: [u8; 40] = tbf_header(b"hotp");
+    static BLINK: [u8; 40] = tbf_header(b"blnk");
+
+    fn process_binary(flash: &'static [u8]) -> ProcessBinary {
+        ProcessBinary {
+            flash,
+            footers: &[],
+            header: tock_tbf::parse::parse_tbf_header(flash, 2).unwrap(),
+        }
+    }
+
+    #[test]
+    fn names_give_stable_short_ids() {
+        let hasher = (|s: &'static str| crc32_posix(s.as_bytes())) as fn(&'static str) -> u32;
+        let assigner = AppIdAssignerNames::new(&hasher);
+
+        let hotp = process_binary(&HOTP);
+        let hotp_reflashed = process_binary(&HOTP_REFLASHED);
+        let blink = process_binary(&BLINK);
+
+        let expected = crc32_posix(b"hotp");
+        assert!(matches!(
+            assigner.to_short_id(&hotp),
+            ShortId::Fixed(id) if id.get() == expected
+        ));
+        assert!(matches!(
+            assigner.to_short_id(&hotp_reflashed),
+            ShortId::Fixed(id) if id.get() == expected
+        ));
+        assert!(!assigner.different_identifier(&hotp, &hotp_reflashed));
+        assert!(assigner.different_identifier(&hotp, &blink));
+    }
+
+    /// Integrity region of an app binary.
+    static BINARY: [u8; 64] = {
+        let mut binary = [0; 64];
+        let mut i = 0;
+        while i < binary.len() {
+            binary[i] = (i * 7) as u8;
+            i += 1;
+        }
+        binary
+    };
+
+    /// `BINARY` with one byte changed.
+    static CORRUPTED: [u8; 64] = {
+        let mut binary = BINARY;
+        binary[40] ^= 1;
+        binary
+    };
+
+    /// SHA256 credentials footer holding the `MockDigest` digest of `BINARY`.
+    static SHA256_FOOTER: [u8; 36] = test_utils::concat(&[
+        &test_utils::credentials::<4>(TbfFooterV2CredentialsType::SHA256, 0),
+        &fold::<32>(&BINARY),
+    ]);
+
+    #[test]
+    fn sha256_detects_corrupted_binary() {
+        let sha = MockDigest::<32>::new();
+        let checker = leak(AppCheckerSha256::new(sha, test_utils::buffer()));
+        let client = RecordingClient::new();
+        DigestDataVerify::set_client(sha, checker);
+        checker.set_client(client);
+
+        for (binary, expected) in [
+            (&BINARY, CheckResult::Accept),
+            (&CORRUPTED, CheckResult::Reject),
+        ] {
+            let credentials = TbfFooterV2Credentials::try_from(&SHA256_FOOTER[..]).unwrap();
+            client.result.set(None);
+            assert!(checker.check_credentials(credentials, binary).is_ok());
+            assert_eq!(client.result.get(), Some(Ok(expected)));
+        }
+    }
+}
@@ -0,0 +1,479 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright Tock Contributors 2024.
+
+//! Cache of credential checking results.
+//!
+//! `CheckCache` wraps another credential checking policy and remembers the
+//! result of the last `N` credential checks. If the same credential is checked
+//! again for the same integrity region, the cached result is returned without
+//! invoking the wrapped policy, and therefore without running the (typically
+//! much slower) signature verifier.
+//!
+//! A cache entry is keyed on the first `DIGEST_PREFIX_LEN` bytes of the
+//! SHA-256 digest of the integrity region, together with the format and the
+//! full bytes of the credential. The cache hashes the integrity region on
+//! every check, so a binary whose contents changed, even at the same location
+//! in flash, never matches a stale entry. The credential bytes are copied into
+//! the cache entry; credentials longer than `C` bytes are always passed to the
+//! wrapped policy and never cached. Entries are evicted in least recently used
+//! order.
+//!
+//! Only results reported by the wrapped policy without an error are cached.
+//!
+//! The cache is kept in RAM only. It starts empty on every boot, so it never
+//! saves work when the kernel checks the applications it finds at boot. It only
+//! helps when the same credentials are checked again while the kernel is
+//! running, for example when applications are loaded or checked again at
+//! runtime. On a miss the integrity region is hashed twice: once by the cache to
+//! compute the key and once more by the wrapped policy.
+//!
+//! The cache sets itself as the client of the hasher `H`, so it needs its own
+//! hasher rather than sharing one with the wrapped policy.
+//!
+//! ### Usage
+//!
+//! ```rust,ignore
+//! let cache = static_init!(
+//!     CheckCache<'static, AppCheckerRsaSimulated<'static>, Sha256Software<'static>, 4, 64>,
+//!     CheckCache::new(rsa_checker, sha, digest_buffer)
+//! );
+//! rsa_checker.set_client(cache);
+//! kernel::hil::digest::DigestDataHash::set_client(sha, cache);
+//! ```
+
+use core::cell::Cell;
+
+use kernel::hil::digest::{ClientData, ClientHash, DigestDataHash, Sha256};
+use kernel::process_checker::{AppCredentialsPolicy, AppCredentialsPolicyClient};
+use kernel::process_checker::{CheckResult, FooterCheckMode};
+use kernel::utilities::cells::{MapCell, OptionalCell, TakeCell};
+use kernel::utilities::leasable_buffer::{SubSlice, SubSliceMut};
+use kernel::ErrorCode;
+use tock_tbf::types::TbfFooterV2Credentials;
+use tock_tbf::types::TbfFooterV2CredentialsType;
+
+/// Number of bytes of the integrity region's SHA-256 digest stored in each
+/// cache entry.
+pub const DIGEST_PREFIX_LEN: usize = 16;
+
+/// Identifies a credential check.
+#[derive(Clone, Copy, PartialEq)]
+struct CacheKey<const C: usize> {
+    binary_digest: [u8; DIGEST_PREFIX_LEN],
+    format: TbfFooterV2CredentialsType,
+    credentials_len: usize,
+    credentials: [u8; C],
+}
+
+impl<const C: usize> CacheKey<C> {
+    /// Returns `None` if the credential is too long to be cached.
+    fn new(digest: &[u8; 32], credentials: &TbfFooterV2Credentials) -> Option<Self> {
+        let data = credentials.data();
+        let mut key = Self {
+            binary_digest: [0; DIGEST_PREFIX_LEN],
+            format: credentials.format(),
+            credentials_len: data.len(),
+            credentials: [0; C],
+        };
+        key.binary_digest
+            .copy_from_slice(&digest[..DIGEST_PREFIX_LEN]);
+        key.credentials.get_mut(..data.len())?.copy_from_slice(data);
+        Some(key)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct CacheEntry<const C: usize> {
+    key: CacheKey<C>,
+    result: CheckResult,
+    /// Value of the cache's use counter when this entry was last used.
+    last_used: u32,
+}
+
+/// Credential checking policy that caches the results of the wrapped policy
+/// `P` in `N` entries, for credentials of up to `C` bytes.
+pub struct CheckCache<
+    'a,
+    P: AppCredentialsPolicy<'static>,
+    H: DigestDataHash<'a, 32> + Sha256,
+    const N: usize,
+    const C: usize,
+> {
+    policy: &'a P,
+    hasher: &'a H,
+    client: OptionalCell<&'static dyn AppCredentialsPolicyClient<'static>>,
+    entries: MapCell<[Option<CacheEntry<C>>; N]>,
+    /// Incremented on every cache access to track entry age.
+    use_counter: Cell<u32>,
+    digest: TakeCell<'static, [u8; 32]>,
+    /// Key of the check the wrapped policy is currently performing.
+    pending_key: OptionalCell<CacheKey<C>>,
+    credentials: OptionalCell<TbfFooterV2Credentials>,
+    binary: OptionalCell<&'static [u8]>,
+}
+
+impl<
+        'a,
+        P: AppCredentialsPolicy<'static>,
+        H: DigestDataHash<'a, 32> + Sha256,
+        const N: usize,
+        const C: usize,
+    > CheckCache<'a, P, H, N, C>
+{
+    pub fn new(policy: &'a P, hasher: &'a H, digest: &'static mut [u8; 32]) -> Self {
+        Self {
+            policy,
+            hasher,
+            client: OptionalCell::empty(),
+            entries: MapCell::new([None; N]),
+            use_counter: Cell::new(0),
+            digest: TakeCell::new(digest),
+            pending_key: OptionalCell::empty(),
+            credentials: OptionalCell::empty(),
+            binary: OptionalCell::empty(),
+        }
+    }
+
+    /// Remove all cached results.
+    pub fn clear(&self) {
+        self.entries.map(|entries| {
+            for entry in entries.iter_mut() {
+                *entry = None;
+            }
+        });
+    }
+
+    fn next_use(&self) -> u32 {
+        let count = self.use_counter.get().wrapping_add(1);
+        self.use_counter.set(count);
+        count
+    }
+
+    /// Return the cached result for `key`, marking the entry as used.
+    fn lookup(&self, key: &CacheKey<C>) -> Option<CheckResult> {
+        let now = self.next_use();
+        self.entries.map_or(None, |entries| {
+            entries.iter_mut().flatten().find_map(|entry| {
+                if entry.key == *key {
+                    entry.last_used = now;
+                    Some(entry.result)
+                } else {
+                    None
+                }
+            })
+        })
+    }
+
+    /// Store `result` for `key`, evicting the least recently used entry if
+    /// the cache is full.
+    fn insert(&self, key: CacheKey<C>, result: CheckResult) {
+        let now = self.next_use();
+        self.entries.map(|entries| {
+            // Empty slots sort first, then entries by age. Measuring age
+            // relative to `now` keeps the order correct when the counter
+            // wraps.
+            let slot = entries
+                .iter_mut()
+                .min_by_key(|entry| entry.map_or(0, |e| e.last_used.wrapping_sub(now)));
+            if let Some(slot) = slot {
+                *slot = Some(CacheEntry {
+                    key,
+                    result,
+                    last_used: now,
+                });
+            }
+        });
+    }
+
+    fn busy(&self) -> bool {
+        self.credentials.is_some()
+    }
+
+    /// Start hashing the integrity region of the credential being checked.
+    fn start_hash(&self, binary: &'static [u8]) -> Result<(), ErrorCode> {
+        self.hasher.set_mode_sha256()?;
+        self.hasher.clear_data();
+        self.hasher
+            .add_data(SubSlice::new(binary))
+            .map_err(|(e, _)| e)
+    }
+
+    /// Hashing finished with the integrity region's digest, or failed if
+    /// `digest` is `None`. Answer from the cache if possible, otherwise check
+    /// with the wrapped policy.
+    fn hashed(&self, digest: Option<&[u8; 32]>) {
+        let (Some(credentials), Some(binary)) = (self.credentials.get(), self.binary.get()) else {
+            return;
+        };
+        let key = digest.and_then(|digest| CacheKey::new(digest, &credentials));
+        if let Some(result) = key.as_ref().and_then(|key| self.lookup(key)) {
+            self.report(Ok(result), credentials, binary);
+            return;
+        }
+
+        if let Some(key) = key {
+            self.pending_key.set(key);
+        }
+        if let Err((e, credentials, binary)) = self.policy.check_credentials(credentials, binary) {
+            self.pending_key.clear();
+            self.report(Err(e), credentials, binary);
+        }
+    }
+
+    fn report(
+        &self,
+        result: Result<CheckResult, ErrorCode>,
+        credentials: TbfFooterV2Credentials,
+        binary: &'static [u8],
+    ) {
+        self.credentials.clear();
+        self.binary.clear();
+        self.client
+            .map(|client| client.check_done(result, credentials, binary));
+    }
+}
+
+impl<
+        'a,
+        P: AppCredentialsPolicy<'static>,
+        H: DigestDataHash<'a, 32> + Sha256,
+        const N: usize,
+        const C: usize,
+    > AppCredentialsPolicy<'static> for CheckCache<'a, P, H, N, C>
+{
+    fn require_credentials(&self) -> bool {
+        self.policy.require_credentials()
+    }
+
+    fn check_credentials(
+        &self,
+        credentials: TbfFooterV2Credentials,
+        binary: &'static [u8],
+    ) -> Result<(), (ErrorCode, TbfFooterV2Credentials, &'static [u8])> {
+        if self.busy() {
+            return Err((ErrorCode::BUSY, credentials, binary));
+        }
+
+        self.credentials.set(credentials);
+        self.binary.set(binary);
+        self.start_hash(binary).map_err(|e| {
+            self.credentials.clear();
+            self.binary.clear();
+            (e, credentials, binary)
+        })
+    }
+
+    fn set_client(&self, client: &'static dyn AppCredentialsPolicyClient<'static>) {
+        self.client.replace(client);
+    }
+
+    fn footer_check_mode(&self) -> FooterCheckMode {
+        self.policy.footer_check_mode()
+    }
+
+    fn decide(&self, results: &[CheckResult]) -> CheckResult {
+        self.policy.decide(results)
+    }
+
+    fn cancel(&self) {
+        // A digest that arrives later is ignored because no check is stored.
+        self.pending_key.clear();
+        self.credentials.clear();
+        self.binary.clear();
+        self.policy.cancel();
+    }
+}
+
+impl<
+        'a,
+        P: AppCredentialsPolicy<'static>,
+        H: DigestDataHash<'a, 32> + Sha256,
+        const N: usize,
+        const C: usize,
+    > ClientData<32> for CheckCache<'a, P, H, N, C>
+{
+    fn add_data_done(&self, result: Result<(), ErrorCode>, _data: SubSlice<'static, u8>) {
+        let started = result.is_ok()
+            && self.digest.take().map_or(false, |digest| {
+                self.hasher
+                    .run(digest)
+                    .map_err(|(_, digest)| {
+                        self.digest.replace(digest);
+                    })
+                    .is_ok()
+            });
+        if !started {
+            // Without a digest the check cannot be cached.
+            self.hashed(None);
+        }
+    }
+
+    fn add_mut_data_done(&self, _result: Result<(), ErrorCode>, _data: SubSliceMut<'static, u8>) {}
+}
+
+impl<
+        'a,
+        P: AppCredentialsPolicy<'static>,
+        H: DigestDataHash<'a, 32> + Sha256,
+        const N: usize,
+        const C: usize,
+    > ClientHash<32> for CheckCache<'a, P, H, N, C>
+{
+    fn hash_done(&self, result: Result<(), ErrorCode>, digest: &'static mut [u8; 32]) {
+        let copy = *digest;
+        self.digest.replace(digest);
+        self.hashed(result.ok().map(|()| &copy));
+    }
+}
+
+impl<
+        'a,
+        P: AppCredentialsPolicy<'static>,
+        H: DigestDataHash<'a, 32> + Sha256,
+        const N: usize,
+        const C: usize,
+    > AppCredentialsPolicyClient<'static> for CheckCache<'a, P, H, N, C>
+{
+    fn check_done(
+        &self,
+        result: Result<CheckResult, ErrorCode>,
+        credentials: TbfFooterV2Credentials,
+        binary: &'static [u8],
+    ) {
+        if !self.busy() {
+            return;
+        }
+        if let (Some(key), Ok(check_result)) = (self.pending_key.take(), result) {
+            self.insert(key, check_result);
+        }
+        self.report(result, credentials, binary);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process_checker::test_utils::{self, leak, MockDigest, MockPolicy, RecordingClient};
+    use capsules_extra::sha256::Sha256Software;
+    use kernel::deferred_call::DeferredCallClient;
+
+    static FOOTER: [u8; 36] = test_utils::credentials(TbfFooterV2CredentialsType::SHA256, 0xa5);
+    static BINARY: [u8; 16] = [0; 16];
+
+    /// A policy whose checks finish when `complete()` is called.
+    fn policy() -> &'static MockPolicy {
+        let policy = MockPolicy::new(TbfFooterV2CredentialsType::SHA256, None);
+        policy.deferred.set(true);
+        policy
+    }
+
+    type TestCache = CheckCache<'static, MockPolicy, MockDigest<32>, 2, 32>;
+
+    fn setup() -> (
+        &'static MockPolicy,
+        &'static TestCache,
+        &'static RecordingClient,
+        &'static MockDigest<32>,
+    ) {
+        let policy = policy();
+        let sha = MockDigest::new();
+        let cache: &'static TestCache = leak(CheckCache::new(policy, sha, test_utils::buffer()));
+        let client = RecordingClient::new();
+        policy.set_client(cache);
+        DigestDataHash::set_client(sha, cache);
+        cache.set_client(client);
+        (policy, cache, client, sha)
+    }
+
+    #[test]
+    fn software_sha256_is_supported() {
+        let policy = policy();
+        let sha = leak(Sha256Software::new());
+        let cache: &'static CheckCache<'static, MockPolicy, Sha256Software<'static>, 2, 32> =
+            leak(CheckCache::new(policy, sha, test_utils::buffer()));
+        let client = RecordingClient::new();
+        policy.set_client(cache);
+        DigestDataHash::set_client(sha, cache);
+        cache.set_client(client);
+
+        let credentials = TbfFooterV2Credentials::try_from(&FOOTER[..]).unwrap();
+        for _ in 0..2 {
+            assert!(cache.check_credentials(credentials, &BINARY).is_ok());
+            // Adding the data and computing the digest each finish in a
+            // deferred call.
+            sha.handle_deferred_call();
+            sha.handle_deferred_call();
+            policy.complete();
+        }
+        assert_eq!(client.results.get(), 2);
+        assert_eq!(client.result.get(), Some(Ok(CheckResult::Accept)));
+        assert_eq!(policy.checks.get(), 1);
+    }
+
+    #[test]
+    fn second_check_is_cached() {
+        let (policy, cache, client, _sha) = setup();
+        let credentials = TbfFooterV2Credentials::try_from(&FOOTER[..]).unwrap();
+
+        assert!(cache.check_credentials(credentials, &BINARY).is_ok());
+        policy.complete();
+        assert_eq!(policy.checks.get(), 1);
+        assert_eq!(client.results.get(), 1);
+
+        // The second check is answered from the cache.
+        assert!(cache.check_credentials(credentials, &BINARY).is_ok());
+        assert_eq!(policy.checks.get(), 1);
+        assert_eq!(client.results.get(), 2);
+        assert_eq!(client.result.get(), Some(Ok(CheckResult::Accept)));
+
+        // After clearing the cache the policy is asked again.
+        cache.clear();
+        assert!(cache.check_credentials(credentials, &BINARY).is_ok());
+        policy.complete();
+        assert_eq!(policy.checks.get(), 2);
+        assert_eq!(client.results.get(), 3);
+    }
+
+    #[test]
+    fn changed_contents_miss_the_cache() {
+        let (policy, cache, client, sha) = setup();
+        let credentials = TbfFooterV2Credentials::try_from(&FOOTER[..]).unwrap();
+
+        assert!(cache.check_credentials(credentials, &BINARY).is_ok());
+        policy.complete();
+        assert_eq!(policy.checks.get(), 1);
+
+        // Same location and length, different contents.
+        sha.rewritten.set(1);
+        assert!(cache.check_credentials(credentials, &BINARY).is_ok());
+        assert_eq!(policy.checks.get(), 2);
+        policy.complete();
+        assert_eq!(client.results.get(), 2);
+    }
+
+    #[test]
+    fn least_recently_used_is_evicted() {
+        let (policy, cache, _client, _sha) = setup();
+        let credentials = TbfFooterV2Credentials::try_from(&FOOTER[..]).unwrap();
+        // Binaries with different contents.
+        static BINARIES: [[u8; 4]; 3] = [[1; 4], [2; 4], [3; 4]];
+
+        for binary in &BINARIES {
+            assert!(cache.check_credentials(credentials, binary).is_ok());
+            policy.complete();
+        }
+        assert_eq!(policy.checks.get(), 3);
+
+        // The most recent binaries are still cached.
+        for binary in &BINARIES[1..] {
+            assert!(cache.check_credentials(credentials, binary).is_ok());
+        }
+        assert_eq!(policy.checks.get(), 3);
+
+        // The first binary was evicted.
+        assert!(cache.check_credentials(credentials, &BINARIES[0]).is_ok());
+        policy.complete();
+        assert_eq!(policy.checks.get(), 4);
+    }
+}
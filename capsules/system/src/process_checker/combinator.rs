@@ -0,0 +1,594 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright Tock Contributors 2024.
+
+//! Credential checking policies that combine two other policies.
+//!
+//! `PolicyOr` accepts a process binary if either inner policy accepts it, and
+//! `PolicyAnd` only accepts a process binary if both inner policies accept it.
+//! The inner policies may accept different credentials, so a binary with a
+//! signature footer and a SHA256 footer satisfies `PolicyAnd` of a signature
+//! checker and a SHA256 checker.
+//!
+//! The combinators use `FooterCheckMode::AllFooters`: each credential (TBF
+//! footer) is checked by the first policy and then, unless the result is
+//! already decided, by the second policy. The combinator records whether each
+//! inner policy accepted or rejected any of the footers, and decides once all
+//! footers have been checked. An inner policy that does not support a
+//! credential (returns `NOSUPPORT` from `check_credentials()`) is treated as if
+//! it returned `CheckResult::Pass`. If neither inner policy supports a
+//! credential, the combinator also returns `NOSUPPORT`.
+//!
+//! The decision is based on state kept by the combinator, so a `CheckCache`
+//! must wrap the inner policies rather than the combinator.
+//!
+//! ### Usage
+//!
+//! ```rust,ignore
+//! let policy = static_init!(
+//!     PolicyOr<'static, AppCheckerSha256, AppCheckerRsaSimulated<'static>>,
+//!     PolicyOr::new(sha_checker, rsa_checker)
+//! );
+//! sha_checker.set_client(policy);
+//! rsa_checker.set_client(policy);
+//! ```
+
+use core::cell::Cell;
+use core::marker::PhantomData;
+
+use kernel::process::{Process, ProcessBinary, ShortId};
+use kernel::process_checker::{AppCredentialsPolicy, AppCredentialsPolicyClient};
+use kernel::process_checker::{AppUniqueness, Compress};
+use kernel::process_checker::{CheckResult, FooterCheckMode};
+use kernel::utilities::cells::OptionalCell;
+use kernel::ErrorCode;
+use tock_tbf::types::TbfFooterV2Credentials;
+
+/// What an inner policy decided about all footers of a process binary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// The policy did not accept or reject any footer.
+    NoCredentials,
+    /// The policy accepted at least one footer and rejected none.
+    Accepted,
+    /// The policy rejected at least one footer.
+    Rejected,
+}
+
+impl Verdict {
+    fn update(self, result: &Result<CheckResult, ErrorCode>) -> Verdict {
+        match (self, result) {
+            (_, Ok(CheckResult::Reject)) => Verdict::Rejected,
+            (Verdict::NoCredentials, Ok(CheckResult::Accept)) => Verdict::Accepted,
+            _ => self,
+        }
+    }
+
+    /// Whether the policy is satisfied, given whether it requires
+    /// credentials.
+    fn satisfied(self, required: bool) -> bool {
+        match self {
+            Verdict::Accepted => true,
+            Verdict::NoCredentials => !required,
+            Verdict::Rejected => false,
+        }
+    }
+}
+
+/// How the results of the two inner policies are combined.
+pub trait CombineOperation {
+    /// Whether credentials are required given whether each inner policy
+    /// requires credentials.
+    fn require_credentials(first: bool, second: bool) -> bool;
+
+    /// Whether the result from the first policy already decides the combined
+    /// result for this footer, so the second policy does not need to check
+    /// the credential.
+    fn is_decided(first: &Result<CheckResult, ErrorCode>) -> bool;
+
+    /// Combine the results from the two inner policies for a single footer.
+    fn combine(
+        first: Result<CheckResult, ErrorCode>,
+        second: Result<CheckResult, ErrorCode>,
+    ) -> Result<CheckResult, ErrorCode>;
+
+    /// Decide about a process binary from the verdicts of the two inner
+    /// policies over all of its footers, and whether each inner policy
+    /// requires credentials. Returning `Pass` leaves the decision to
+    /// `require_credentials()`.
+    fn decide(
+        first: Verdict,
+        first_required: bool,
+        second: Verdict,
+        second_required: bool,
+    ) -> CheckResult;
+}
+
+/// Accept if either inner policy accepts.
+pub struct Or;
+
+impl CombineOperation for Or {
+    fn require_credentials(first: bool, second: bool) -> bool {
+        // If either policy is satisfied without credentials, so is the
+        // combination.
+        first && second
+    }
+
+    fn is_decided(first: &Result<CheckResult, ErrorCode>) -> bool {
+        matches!(first, Ok(CheckResult::Accept))
+    }
+
+    fn combine(
+        first: Result<CheckResult, ErrorCode>,
+        second: Result<CheckResult, ErrorCode>,
+    ) -> Result<CheckResult, ErrorCode> {
+        match (first, second) {
+            (Ok(CheckResult::Accept), _) | (_, Ok(CheckResult::Accept)) => Ok(CheckResult::Accept),
+            (Ok(CheckResult::Reject), _) | (_, Ok(CheckResult::Reject)) => Ok(CheckResult::Reject),
+            (Err(e), Err(_)) => Err(e),
+            _ => Ok(CheckResult::Pass),
+        }
+    }
+
+    fn decide(
+        first: Verdict,
+        _first_required: bool,
+        second: Verdict,
+        _second_required: bool,
+    ) -> CheckResult {
+        match (first, second) {
+            (Verdict::Accepted, _) | (_, Verdict::Accepted) => CheckResult::Accept,
+            (Verdict::Rejected, _) | (_, Verdict::Rejected) => CheckResult::Reject,
+            _ => CheckResult::Pass,
+        }
+    }
+}
+
+/// Accept only if both inner policies accept.
+pub struct And;
+
+impl CombineOperation for And {
+    fn require_credentials(first: bool, second: bool) -> bool {
+        first || second
+    }
+
+    fn is_decided(first: &Result<CheckResult, ErrorCode>) -> bool {
+        matches!(first, Ok(CheckResult::Reject))
+    }
+
+    fn combine(
+        first: Result<CheckResult, ErrorCode>,
+        second: Result<CheckResult, ErrorCode>,
+    ) -> Result<CheckResult, ErrorCode> {
+        match (first, second) {
+            (Ok(CheckResult::Reject), _) | (_, Ok(CheckResult::Reject)) => Ok(CheckResult::Reject),
+            (Ok(CheckResult::Accept), Ok(CheckResult::Accept)) => Ok(CheckResult::Accept),
+            (Err(e), _) | (_, Err(e)) => Err(e),
+            _ => Ok(CheckResult::Pass),
+        }
+    }
+
+    fn decide(
+        first: Verdict,
+        first_required: bool,
+        second: Verdict,
+        second_required: bool,
+    ) -> CheckResult {
+        if first == Verdict::Rejected || second == Verdict::Rejected {
+            CheckResult::Reject
+        } else if first.satisfied(first_required) && second.satisfied(second_required) {
+            if first == Verdict::Accepted || second == Verdict::Accepted {
+                CheckResult::Accept
+            } else {
+                // Neither policy requires credentials.
+                CheckResult::Pass
+            }
+        } else {
+            // A policy that requires credentials did not accept any, so
+            // `require_credentials()` rejects the binary.
+            CheckResult::Pass
+        }
+    }
+}
+
+/// Credential checking policy that accepts if either inner policy accepts.
+pub type PolicyOr<'a, A, B> = PolicyCombinator<'a, A, B, Or>;
+
+/// Credential checking policy that accepts only if both inner policies accept.
+pub type PolicyAnd<'a, A, B> = PolicyCombinator<'a, A, B, And>;
+
+/// Which inner policy is currently checking a credential.
+#[derive(Clone, Copy)]
+enum State {
+    CheckingFirst,
+    CheckingSecond,
+}
+
+/// Combines two credential checking policies with the operation `O`.
+///
+/// Use the `PolicyOr` and `PolicyAnd` aliases rather than naming this type
+/// directly. Both inner policies must have their client set to this
+/// combinator.
+pub struct PolicyCombinator<
+    'a,
+    A: AppCredentialsPolicy<'static>,
+    B: AppCredentialsPolicy<'static>,
+    O: CombineOperation,
+> {
+    first: &'a A,
+    second: &'a B,
+    client: OptionalCell<&'static dyn AppCredentialsPolicyClient<'static>>,
+    state: OptionalCell<State>,
+    first_result: OptionalCell<Result<CheckResult, ErrorCode>>,
+    credentials: OptionalCell<TbfFooterV2Credentials>,
+    binary: OptionalCell<&'static [u8]>,
+    /// The process binary whose footers are being checked.
+    checked_binary: OptionalCell<&'static [u8]>,
+    /// Verdicts of the first and second policy over the footers of
+    /// `checked_binary` checked so far.
+    verdicts: Cell<(Verdict, Verdict)>,
+    _operation: PhantomData<O>,
+}
+
+impl<
+        'a,
+        A: AppCredentialsPolicy<'static>,
+        B: AppCredentialsPolicy<'static>,
+        O: CombineOperation,
+    > PolicyCombinator<'a, A, B, O>
+{
+    pub fn new(first: &'a A, second: &'a B) -> Self {
+        Self {
+            first,
+            second,
+            client: OptionalCell::empty(),
+            state: OptionalCell::empty(),
+            first_result: OptionalCell::empty(),
+            credentials: OptionalCell::empty(),
+            binary: OptionalCell::empty(),
+            checked_binary: OptionalCell::empty(),
+            verdicts: Cell::new((Verdict::NoCredentials, Verdict::NoCredentials)),
+            _operation: PhantomData,
+        }
+    }
+
+    /// Start collecting verdicts for `binary` unless they are already being
+    /// collected for it.
+    fn start_binary(&self, binary: &'static [u8]) {
+        let same = self.checked_binary.map_or(false, |checked| {
+            checked.as_ptr() == binary.as_ptr() && checked.len() == binary.len()
+        });
+        if !same {
+            self.checked_binary.set(binary);
+            self.verdicts
+                .set((Verdict::NoCredentials, Verdict::NoCredentials));
+        }
+    }
+
+    /// Start checking the stored credential with the second policy.
+    ///
+    /// If the second policy cannot start a check, the combined result is
+    /// reported immediately.
+    fn check_second(&self, credentials: TbfFooterV2Credentials, binary: &'static [u8]) {
+        self.state.set(State::CheckingSecond);
+        match self.second.check_credentials(credentials, binary) {
+            Ok(()) => {}
+            Err((ErrorCode::NOSUPPORT, _, _)) => self.second_done(Ok(CheckResult::Pass)),
+            Err((e, _, _)) => self.second_done(Err(e)),
+        }
+    }
+
+    fn second_done(&self, result: Result<CheckResult, ErrorCode>) {
+        let (first_verdict, second_verdict) = self.verdicts.get();
+        self.verdicts
+            .set((first_verdict, second_verdict.update(&result)));
+        let first = self.first_result.take().unwrap_or(Ok(CheckResult::Pass));
+        self.finish(O::combine(first, result));
+    }
+
+    fn finish(&self, result: Result<CheckResult, ErrorCode>) {
+        self.state.clear();
+        if let (Some(credentials), Some(binary)) = (self.credentials.take(), self.binary.take()) {
+            self.client
+                .map(|client| client.check_done(result, credentials, binary));
+        }
+    }
+}
+
+impl<
+        'a,
+        A: AppCredentialsPolicy<'static>,
+        B: AppCredentialsPolicy<'static>,
+        O: CombineOperation,
+    > AppCredentialsPolicy<'static> for PolicyCombinator<'a, A, B, O>
+{
+    fn require_credentials(&self) -> bool {
+        O::require_credentials(
+            self.first.require_credentials(),
+            self.second.require_credentials(),
+        )
+    }
+
+    fn check_credentials(
+        &self,
+        credentials: TbfFooterV2Credentials,
+        binary: &'static [u8],
+    ) -> Result<(), (ErrorCode, TbfFooterV2Credentials, &'static [u8])> {
+        if self.state.is_some() {
+            return Err((ErrorCode::BUSY, credentials, binary));
+        }
+
+        self.start_binary(binary);
+        self.credentials.set(credentials);
+        self.binary.set(binary);
+        self.state.set(State::CheckingFirst);
+
+        match self.first.check_credentials(credentials, binary) {
+            Ok(()) => Ok(()),
+            Err((ErrorCode::NOSUPPORT, _, _)) => {
+                // The first policy does not check this credential, so only
+                // the second policy can decide.
+                self.first_result.set(Ok(CheckResult::Pass));
+                self.state.set(State::CheckingSecond);
+                match self.second.check_credentials(credentials, binary) {
+                    Ok(()) => Ok(()),
+                    Err((e, _, _)) => {
+                        self.first_result.clear();
+                        self.credentials.clear();
+                        self.binary.clear();
+                        self.state.clear();
+                        Err((e, credentials, binary))
+                    }
+                }
+            }
+            Err((e, _, _)) => {
+                self.credentials.clear();
+                self.binary.clear();
+                self.state.clear();
+                Err((e, credentials, binary))
+            }
+        }
+    }
+
+    fn set_client(&self, client: &'static dyn AppCredentialsPolicyClient<'static>) {
+        self.client.replace(client);
+    }
+
+    fn footer_check_mode(&self) -> FooterCheckMode {
+        FooterCheckMode::AllFooters
+    }
+
+    fn decide(&self, _results: &[CheckResult]) -> CheckResult {
+        let (first, second) = self.verdicts.get();
+        self.checked_binary.clear();
+        self.verdicts
+            .set((Verdict::NoCredentials, Verdict::NoCredentials));
+        O::decide(
+            first,
+            self.first.require_credentials(),
+            second,
+            self.second.require_credentials(),
+        )
+    }
+
+    fn cancel(&self) {
+        self.state.clear();
+        self.first_result.clear();
+        self.credentials.clear();
+        self.binary.clear();
+        self.checked_binary.clear();
+        self.verdicts
+            .set((Verdict::NoCredentials, Verdict::NoCredentials));
+        self.first.cancel();
+        self.second.cancel();
+    }
+}
+
+impl<
+        'a,
+        A: AppCredentialsPolicy<'static>,
+        B: AppCredentialsPolicy<'static>,
+        O: CombineOperation,
+    > AppCredentialsPolicyClient<'static> for PolicyCombinator<'a, A, B, O>
+{
+    fn check_done(
+        &self,
+        result: Result<CheckResult, ErrorCode>,
+        credentials: TbfFooterV2Credentials,
+        binary: &'static [u8],
+    ) {
+        // Ignore a late result for a check that was cancelled.
+        let current = self
+            .binary
+            .map_or(false, |checked| checked.as_ptr() == binary.as_ptr())
+            && self.credentials.map_or(false, |checked| {
+                checked.data().as_ptr() == credentials.data().as_ptr()
+            });
+        if !current {
+            return;
+        }
+
+        match self.state.get() {
+            Some(State::CheckingFirst) => {
+                let (first_verdict, second_verdict) = self.verdicts.get();
+                self.verdicts
+                    .set((first_verdict.update(&result), second_verdict));
+                if O::is_decided(&result) {
+                    self.finish(result);
+                } else {
+                    self.first_result.set(result);
+                    self.check_second(credentials, binary);
+                }
+            }
+            Some(State::CheckingSecond) => self.second_done(result),
+            None => {}
+        }
+    }
+}
+
+impl<
+        'a,
+        A: AppCredentialsPolicy<'static> + AppUniqueness,
+        B: AppCredentialsPolicy<'static> + AppUniqueness,
+        O: CombineOperation,
+    > AppUniqueness for PolicyCombinator<'a, A, B, O>
+{
+    // Two processes can only run concurrently if neither policy considers
+    // them to have the same identifier.
+    fn different_identifier(&self, process_a: &ProcessBinary, process_b: &ProcessBinary) -> bool {
+        self.first.different_identifier(process_a, process_b)
+            && self.second.different_identifier(process_a, process_b)
+    }
+
+    fn different_identifier_process(
+        &self,
+        process_a: &ProcessBinary,
+        process_b: &dyn Process,
+    ) -> bool {
+        self.first
+            .different_identifier_process(process_a, process_b)
+            && self
+                .second
+                .different_identifier_process(process_a, process_b)
+    }
+
+    fn different_identifier_processes(
+        &self,
+        process_a: &dyn Process,
+        process_b: &dyn Process,
+    ) -> bool {
+        self.first
+            .different_identifier_processes(process_a, process_b)
+            && self
+                .second
+                .different_identifier_processes(process_a, process_b)
+    }
+}
+
+impl<
+        'a,
+        A: AppCredentialsPolicy<'static> + Compress,
+        B: AppCredentialsPolicy<'static> + Compress,
+        O: CombineOperation,
+    > Compress for PolicyCombinator<'a, A, B, O>
+{
+    // Use the ShortId from the first policy, unless it does not assign one.
+    fn to_short_id(&self, process: &ProcessBinary) -> ShortId {
+        match self.first.to_short_id(process) {
+            ShortId::LocallyUnique => self.second.to_short_id(process),
+            short_id => short_id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process_checker::test_utils::{check, concat, footer, leak, MockPolicy, APP};
+    use tock_tbf::types::TbfFooterV2CredentialsType;
+
+    const SHA256_FOOTER: [u8; 40] = footer(TbfFooterV2CredentialsType::SHA256);
+    const SHA512_FOOTER: [u8; 72] = footer(TbfFooterV2CredentialsType::SHA512);
+
+    static SHA256_ONLY: [u8; 40] = SHA256_FOOTER;
+    static SHA256_AND_SHA512: [u8; 112] = concat(&[&SHA256_FOOTER, &SHA512_FOOTER]);
+
+    fn policy(
+        accept: TbfFooterV2CredentialsType,
+        reject: Option<TbfFooterV2CredentialsType>,
+    ) -> &'static MockPolicy {
+        MockPolicy::new(accept, reject)
+    }
+
+    fn and(
+        first: &'static MockPolicy,
+        second: &'static MockPolicy,
+    ) -> &'static PolicyAnd<'static, MockPolicy, MockPolicy> {
+        let and = leak(PolicyAnd::new(first, second));
+        first.set_client(and);
+        second.set_client(and);
+        and
+    }
+
+    fn or(
+        first: &'static MockPolicy,
+        second: &'static MockPolicy,
+    ) -> &'static PolicyOr<'static, MockPolicy, MockPolicy> {
+        let or = leak(PolicyOr::new(first, second));
+        first.set_client(or);
+        second.set_client(or);
+        or
+    }
+    #[test]
+    fn and_accepts_credentials_in_separate_footers() {
+        let sha256 = policy(TbfFooterV2CredentialsType::SHA256, None);
+        let sha512 = policy(TbfFooterV2CredentialsType::SHA512, None);
+        assert_eq!(check(and(sha256, sha512), &SHA256_AND_SHA512), Some(Ok(())));
+    }
+
+    #[test]
+    fn and_requires_both_credentials() {
+        let sha256 = policy(TbfFooterV2CredentialsType::SHA256, None);
+        let sha512 = policy(TbfFooterV2CredentialsType::SHA512, None);
+        assert_eq!(check(and(sha256, sha512), &SHA256_ONLY), Some(Err(None)));
+    }
+
+    #[test]
+    fn and_rejects_if_either_rejects() {
+        let sha256 = policy(TbfFooterV2CredentialsType::SHA256, None);
+        let sha512 = policy(
+            TbfFooterV2CredentialsType::Reserved,
+            Some(TbfFooterV2CredentialsType::SHA512),
+        );
+        assert_eq!(
+            check(and(sha256, sha512), &SHA256_AND_SHA512),
+            Some(Err(Some(1)))
+        );
+    }
+
+    #[test]
+    fn or_accepts_either_credential() {
+        let sha256 = policy(TbfFooterV2CredentialsType::SHA256, None);
+        let sha512 = policy(TbfFooterV2CredentialsType::SHA512, None);
+        assert_eq!(check(or(sha256, sha512), &SHA256_ONLY), Some(Ok(())));
+
+        let sha256 = policy(TbfFooterV2CredentialsType::SHA256, None);
+        let sha512 = policy(
+            TbfFooterV2CredentialsType::Reserved,
+            Some(TbfFooterV2CredentialsType::SHA512),
+        );
+        assert_eq!(check(or(sha256, sha512), &SHA256_AND_SHA512), Some(Ok(())));
+    }
+
+    #[test]
+    fn unsupported_credentials_are_not_accepted() {
+        let sha384 = policy(TbfFooterV2CredentialsType::SHA384, None);
+        let sha512 = policy(TbfFooterV2CredentialsType::SHA512, None);
+        assert_eq!(check(or(sha384, sha512), &SHA256_ONLY), Some(Err(None)));
+
+        let sha384 = policy(TbfFooterV2CredentialsType::SHA384, None);
+        let sha512 = policy(TbfFooterV2CredentialsType::SHA512, None);
+        assert_eq!(check(and(sha384, sha512), &SHA256_ONLY), Some(Err(None)));
+    }
+
+    #[test]
+    fn cancel_stops_a_hung_check() {
+        let sha256 = policy(TbfFooterV2CredentialsType::SHA256, None);
+        let sha512 = policy(TbfFooterV2CredentialsType::SHA512, None);
+        let and = and(sha256, sha512);
+        let (credentials, _) = tock_tbf::parse::parse_tbf_footer(&SHA256_ONLY).unwrap();
+
+        // The first policy never finishes the check.
+        sha256.deferred.set(true);
+        assert!(and.check_credentials(credentials, &APP).is_ok());
+        assert!(matches!(
+            and.check_credentials(credentials, &APP),
+            Err((ErrorCode::BUSY, _, _))
+        ));
+
+        // After the check is cancelled, the next binary is checked normally.
+        and.cancel();
+        sha256.deferred.set(false);
+        assert_eq!(check(and, &SHA256_AND_SHA512), Some(Ok(())));
+    }
+}
@@ -2,6 +2,13 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 // Copyright Tock Contributors 2024.
 
+pub mod allowlist;
 pub mod basic;
+pub mod cache;
+pub mod combinator;
 pub mod signature;
 pub mod tbf;
+pub mod timeout;
+
+#[cfg(test)]
+mod test_utils;
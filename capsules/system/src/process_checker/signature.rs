@@ -4,6 +4,7 @@
 
 //! Signature credential checker for checking process credentials.
 
+use kernel::deferred_call::{DeferredCall, DeferredCallClient};
 use kernel::hil;
 use kernel::process_checker::CheckResult;
 use kernel::process_checker::{AppCredentialsPolicy, AppCredentialsPolicyClient};
@@ -19,9 +20,17 @@ use tock_tbf::types::TbfFooterV2CredentialsType;
 /// This checker provides the scaffolding on top of a hasher (`&H`) and a
 /// verifier (`&S`) for a given `TbfFooterV2CredentialsType`.
 ///
-/// This assumes the `TbfFooterV2CredentialsType` data format only contains the
-/// signature (i.e. the data length of the credential in the TBF footer is the
-/// same as `SL`).
+/// The credential data in the TBF footer may contain more than the signature.
+/// The first `metadata_len` bytes of the credential data are treated as
+/// metadata (for example a key id) and are available through `metadata()`
+/// while a check is in progress. The signature is the `SL` bytes starting at
+/// `signature_offset` in the credential data. If the credential only contains
+/// the signature, `metadata_len` and `signature_offset` are 0.
+///
+/// Credentials whose data is too short to contain the metadata and the
+/// signature are not checked, and the checker returns `CheckResult::Pass`.
+/// This result is delivered from a deferred call, so boards must call
+/// `register()` on the checker.
 pub struct AppCheckerSignature<
     'a,
     S: hil::public_key_crypto::signature::SignatureVerify<'static, HL, SL>,
@@ -35,8 +44,11 @@ pub struct AppCheckerSignature<
     signature: MapCell<&'static mut [u8; SL]>,
     client: OptionalCell<&'static dyn AppCredentialsPolicyClient<'static>>,
     credential_type: TbfFooterV2CredentialsType,
+    metadata_len: usize,
+    signature_offset: usize,
     credentials: OptionalCell<TbfFooterV2Credentials>,
     binary: OptionalCell<&'static [u8]>,
+    deferred_call: DeferredCall,
 }
 
 impl<
@@ -47,12 +59,20 @@ impl<
         const SL: usize,
     > AppCheckerSignature<'a, S, H, HL, SL>
 {
+    /// Create a checker for credentials of type `credential_type` whose data
+    /// starts with `metadata_len` bytes of metadata and contains the signature
+    /// at `signature_offset`.
+    ///
+    /// The checker uses a deferred call, so `register()` must be called
+    /// before checking credentials. `AppCheckerSignatureComponent` does this.
     pub fn new(
         hasher: &'a H,
         verifier: &'a S,
         hash_buffer: &'static mut [u8; HL],
         signature_buffer: &'static mut [u8; SL],
         credential_type: TbfFooterV2CredentialsType,
+        metadata_len: usize,
+        signature_offset: usize,
     ) -> AppCheckerSignature<'a, S, H, HL, SL> {
         Self {
             hasher,
@@ -61,9 +81,58 @@ impl<
             signature: MapCell::new(signature_buffer),
             client: OptionalCell::empty(),
             credential_type,
+            metadata_len,
+            signature_offset,
             credentials: OptionalCell::empty(),
             binary: OptionalCell::empty(),
+            deferred_call: DeferredCall::new(),
+        }
+    }
+
+    /// The metadata of the credential currently being checked, if any.
+    ///
+    /// Returns `None` if the credential is too short to contain the metadata.
+    pub fn metadata(&self) -> Option<&'static [u8]> {
+        self.credentials
+            .and_then(|credentials| credentials.data().get(..self.metadata_len))
+    }
+
+    /// The signature in the credential data, if the data contains both the
+    /// metadata and a complete signature.
+    fn signature(&self, credentials: &TbfFooterV2Credentials) -> Option<&'static [u8]> {
+        let data = credentials.data();
+        if data.len() < self.metadata_len + SL {
+            return None;
         }
+        data.get(self.signature_offset..self.signature_offset + SL)
+    }
+
+    /// Report `result` for the credential being checked.
+    ///
+    /// Callbacks that arrive when no check is in progress are ignored.
+    fn finish(&self, result: Result<CheckResult, ErrorCode>) {
+        if let (Some(credentials), Some(binary)) = (self.credentials.take(), self.binary.take()) {
+            self.client
+                .map(|c| c.check_done(result, credentials, binary));
+        }
+    }
+}
+
+impl<
+        'a,
+        S: hil::public_key_crypto::signature::SignatureVerify<'static, HL, SL>,
+        H: hil::digest::DigestDataHash<'a, HL>,
+        const HL: usize,
+        const SL: usize,
+    > DeferredCallClient for AppCheckerSignature<'a, S, H, HL, SL>
+{
+    fn handle_deferred_call(&self) {
+        // The credential was too short to contain a signature.
+        self.finish(Ok(CheckResult::Pass));
+    }
+
+    fn register(&'static self) {
+        self.deferred_call.register(self);
     }
 }
 
@@ -78,28 +147,21 @@ impl<
     fn add_mut_data_done(&self, _result: Result<(), ErrorCode>, _data: SubSliceMut<'static, u8>) {}
 
     fn add_data_done(&self, result: Result<(), ErrorCode>, data: SubSlice<'static, u8>) {
+        if self.credentials.is_none() {
+            return;
+        }
         self.binary.set(data.take());
 
         // We added the binary data to the hasher, now we can compute the hash.
-        match result {
-            Err(e) => {
-                self.client.map(|c| {
-                    let binary = self.binary.take().unwrap();
-                    let cred = self.credentials.take().unwrap();
-                    c.check_done(Err(e), cred, binary)
-                });
-            }
-            Ok(()) => {
-                self.hash.take().map(|h| {
-                    if let Err((e, _)) = self.hasher.run(h) {
-                        self.client.map(|c| {
-                            let binary = self.binary.take().unwrap();
-                            let cred = self.credentials.take().unwrap();
-                            c.check_done(Err(e), cred, binary)
-                        });
-                    }
-                });
-            }
+        let result = result.and_then(|()| {
+            let hash = self.hash.take().ok_or(ErrorCode::FAIL)?;
+            self.hasher.run(hash).map_err(|(e, hash)| {
+                self.hash.replace(hash);
+                e
+            })
+        });
+        if let Err(e) = result {
+            self.finish(Err(e));
         }
     }
 }
@@ -113,36 +175,25 @@ impl<
     > hil::digest::ClientHash<HL> for AppCheckerSignature<'a, S, H, HL, SL>
 {
     fn hash_done(&self, result: Result<(), ErrorCode>, digest: &'static mut [u8; HL]) {
-        match result {
-            Err(e) => {
-                self.hash.replace(digest);
-                self.client.map(|c| {
-                    let binary = self.binary.take().unwrap();
-                    let cred = self.credentials.take().unwrap();
-                    c.check_done(Err(e), cred, binary)
-                });
-            }
-            Ok(()) => match self.signature.take() {
-                Some(sig) => {
-                    if let Err((e, d, s)) = self.verifier.verify(digest, sig) {
-                        self.hash.replace(d);
-                        self.signature.replace(s);
-                        self.client.map(|c| {
-                            let binary = self.binary.take().unwrap();
-                            let cred = self.credentials.take().unwrap();
-                            c.check_done(Err(e), cred, binary)
-                        });
-                    }
+        if self.credentials.is_none() {
+            self.hash.replace(digest);
+            return;
+        }
+        match (result, self.signature.take()) {
+            (Ok(()), Some(sig)) => {
+                if let Err((e, d, s)) = self.verifier.verify(digest, sig) {
+                    self.hash.replace(d);
+                    self.signature.replace(s);
+                    self.finish(Err(e));
                 }
-                None => {
-                    self.hash.replace(digest);
-                    self.client.map(|c| {
-                        let binary = self.binary.take().unwrap();
-                        let cred = self.credentials.take().unwrap();
-                        c.check_done(Err(ErrorCode::FAIL), cred, binary)
-                    });
+            }
+            (result, sig) => {
+                self.hash.replace(digest);
+                if let Some(sig) = sig {
+                    self.signature.replace(sig);
                 }
-            },
+                self.finish(Err(result.err().unwrap_or(ErrorCode::FAIL)));
+            }
         }
     }
 }
@@ -179,17 +230,12 @@ impl<
         self.hash.replace(hash);
         self.signature.replace(signature);
 
-        self.client.map(|c| {
-            let binary = self.binary.take().unwrap();
-            let cred = self.credentials.take().unwrap();
-            let check_result = if result.unwrap_or(false) {
-                Ok(CheckResult::Accept)
-            } else {
-                Ok(CheckResult::Pass)
-            };
-
-            c.check_done(check_result, cred, binary)
-        });
+        let check_result = if result.unwrap_or(false) {
+            CheckResult::Accept
+        } else {
+            CheckResult::Pass
+        };
+        self.finish(Ok(check_result));
     }
 }
 
@@ -210,26 +256,224 @@ impl<
         credentials: TbfFooterV2Credentials,
         binary: &'static [u8],
     ) -> Result<(), (ErrorCode, TbfFooterV2Credentials, &'static [u8])> {
+        if credentials.format() != self.credential_type {
+            return Err((ErrorCode::NOSUPPORT, credentials, binary));
+        }
+        if self.credentials.is_some() {
+            return Err((ErrorCode::BUSY, credentials, binary));
+        }
+
         self.credentials.set(credentials);
 
-        if credentials.format() == self.credential_type {
-            // Save the signature we are trying to compare with.
-            self.signature.map(|b| {
-                b.as_mut_slice()[..SL].copy_from_slice(&credentials.data()[..SL]);
-            });
-
-            // Add the process binary to compute the hash.
-            self.hasher.clear_data();
-            match self.hasher.add_data(SubSlice::new(binary)) {
-                Ok(()) => Ok(()),
-                Err((e, b)) => Err((e, credentials, b.take())),
+        let Some(signature) = self.signature(&credentials) else {
+            self.binary.set(binary);
+            self.deferred_call.set();
+            return Ok(());
+        };
+
+        // Save the signature we are trying to compare with.
+        self.signature.map(|b| {
+            b.copy_from_slice(signature);
+        });
+
+        // Add the process binary to compute the hash.
+        self.hasher.clear_data();
+        match self.hasher.add_data(SubSlice::new(binary)) {
+            Ok(()) => Ok(()),
+            Err((e, b)) => {
+                self.credentials.clear();
+                Err((e, credentials, b.take()))
             }
-        } else {
-            Err((ErrorCode::NOSUPPORT, credentials, binary))
         }
     }
 
     fn set_client(&self, client: &'static dyn AppCredentialsPolicyClient<'static>) {
         self.client.replace(client);
     }
+
+    fn cancel(&self) {
+        // Callbacks for the cancelled check return the buffers and are
+        // otherwise ignored.
+        self.credentials.clear();
+        self.binary.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process_checker::test_utils::{self, leak, MockDigest, RecordingClient};
+    use core::cell::Cell;
+    use kernel::hil::digest::{ClientData, ClientHash, DigestDataHash};
+    use kernel::hil::public_key_crypto::signature::{ClientVerify, SignatureVerify};
+
+    const HL: usize = 4;
+    const SL: usize = 16;
+    const METADATA_LEN: usize = 4;
+    const SIGNATURE_OFFSET: usize = 64 - SL;
+
+    /// SHA512 credential: 4 bytes of metadata, 44 bytes of padding and a 16
+    /// byte signature at the end.
+    static FOOTER: [u8; 68] = {
+        let mut footer = [0; 68];
+        // TbfFooterV2CredentialsType::SHA512
+        footer[0] = 5;
+        let mut i = 0;
+        while i < 64 {
+            footer[4 + i] = if i < METADATA_LEN {
+                0xee
+            } else if i < 64 - SL {
+                0xaa
+            } else {
+                i as u8
+            };
+            i += 1;
+        }
+        footer
+    };
+    static BINARY: [u8; 16] = [0; 16];
+
+    /// Verifier that records the signature it was given and accepts it if it
+    /// matches `expected`.
+    struct MockVerifier {
+        expected: [u8; SL],
+        received: Cell<Option<[u8; SL]>>,
+        client: OptionalCell<&'static dyn ClientVerify<HL, SL>>,
+    }
+
+    impl SignatureVerify<'static, HL, SL> for MockVerifier {
+        fn set_verify_client(&self, client: &'static dyn ClientVerify<HL, SL>) {
+            self.client.set(client);
+        }
+
+        fn verify(
+            &self,
+            hash: &'static mut [u8; HL],
+            signature: &'static mut [u8; SL],
+        ) -> Result<(), (ErrorCode, &'static mut [u8; HL], &'static mut [u8; SL])> {
+            self.received.set(Some(*signature));
+            let valid = *signature == self.expected;
+            self.client
+                .map(|c| c.verification_done(Ok(valid), hash, signature));
+            Ok(())
+        }
+    }
+
+    type TestChecker = AppCheckerSignature<'static, MockVerifier, MockDigest<HL>, HL, SL>;
+
+    fn setup(
+        metadata_len: usize,
+        signature_offset: usize,
+    ) -> (
+        &'static TestChecker,
+        &'static MockVerifier,
+        &'static RecordingClient,
+    ) {
+        // The digest of `BINARY` is all zeros.
+        let hasher = MockDigest::new();
+        let mut expected = [0; SL];
+        for (i, byte) in expected.iter_mut().enumerate() {
+            *byte = (64 - SL + i) as u8;
+        }
+        let verifier = leak(MockVerifier {
+            expected,
+            received: Cell::new(None),
+            client: OptionalCell::empty(),
+        });
+        let checker: &'static TestChecker = leak(AppCheckerSignature::new(
+            hasher,
+            verifier,
+            test_utils::buffer(),
+            test_utils::buffer(),
+            TbfFooterV2CredentialsType::SHA512,
+            metadata_len,
+            signature_offset,
+        ));
+        let client = RecordingClient::new();
+        DigestDataHash::set_client(hasher, checker);
+        verifier.set_verify_client(checker);
+        checker.set_client(client);
+        (checker, verifier, client)
+    }
+
+    #[test]
+    fn signature_is_read_at_the_offset() {
+        let (checker, verifier, client) = setup(METADATA_LEN, SIGNATURE_OFFSET);
+        let credentials = TbfFooterV2Credentials::try_from(&FOOTER[..]).unwrap();
+
+        assert!(checker.check_credentials(credentials, &BINARY).is_ok());
+        assert_eq!(verifier.received.get(), Some(verifier.expected));
+        assert_eq!(client.result.get(), Some(Ok(CheckResult::Accept)));
+        assert_eq!(client.results.get(), 1);
+        assert!(checker.metadata().is_none());
+    }
+
+    #[test]
+    fn misplaced_signature_is_not_accepted() {
+        let (checker, verifier, client) = setup(METADATA_LEN, METADATA_LEN);
+        let credentials = TbfFooterV2Credentials::try_from(&FOOTER[..]).unwrap();
+
+        assert!(checker.check_credentials(credentials, &BINARY).is_ok());
+        assert_ne!(verifier.received.get(), Some(verifier.expected));
+        assert_eq!(client.result.get(), Some(Ok(CheckResult::Pass)));
+        assert_eq!(client.results.get(), 1);
+    }
+
+    #[test]
+    fn short_credential_passes() {
+        let (checker, verifier, client) = setup(64 - SL + 1, SIGNATURE_OFFSET);
+        let credentials = TbfFooterV2Credentials::try_from(&FOOTER[..]).unwrap();
+
+        assert!(checker.check_credentials(credentials, &BINARY).is_ok());
+        assert_eq!(checker.metadata().map(|m| m.len()), Some(64 - SL + 1));
+        checker.handle_deferred_call();
+        assert_eq!(verifier.received.get(), None);
+        assert_eq!(client.result.get(), Some(Ok(CheckResult::Pass)));
+        assert_eq!(client.results.get(), 1);
+    }
+
+    #[test]
+    fn metadata_longer_than_the_credential_is_not_returned() {
+        let (checker, verifier, client) = setup(65, SIGNATURE_OFFSET);
+        let credentials = TbfFooterV2Credentials::try_from(&FOOTER[..]).unwrap();
+
+        assert!(checker.check_credentials(credentials, &BINARY).is_ok());
+        assert_eq!(checker.metadata(), None);
+        checker.handle_deferred_call();
+        assert_eq!(verifier.received.get(), None);
+        assert_eq!(client.result.get(), Some(Ok(CheckResult::Pass)));
+        assert_eq!(client.results.get(), 1);
+    }
+
+    #[test]
+    fn spurious_callbacks_are_ignored() {
+        let (checker, _verifier, client) = setup(METADATA_LEN, SIGNATURE_OFFSET);
+
+        checker.handle_deferred_call();
+        ClientVerify::verification_done(
+            checker,
+            Ok(true),
+            test_utils::buffer(),
+            test_utils::buffer(),
+        );
+        checker.hash_done(Ok(()), test_utils::buffer());
+        checker.add_data_done(Ok(()), SubSlice::new(&BINARY));
+        assert_eq!(client.result.get(), None);
+    }
+
+    #[test]
+    fn other_credential_types_are_not_supported() {
+        let (checker, _verifier, client) = setup(METADATA_LEN, SIGNATURE_OFFSET);
+        let mut footer = FOOTER;
+        // TbfFooterV2CredentialsType::SHA256
+        footer[0] = 3;
+        let footer: &'static [u8; 68] = leak(footer);
+        let credentials = TbfFooterV2Credentials::try_from(&footer[..36]).unwrap();
+
+        assert!(matches!(
+            checker.check_credentials(credentials, &BINARY),
+            Err((ErrorCode::NOSUPPORT, _, _))
+        ));
+        assert_eq!(client.result.get(), None);
+    }
 }
@@ -0,0 +1,387 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright Tock Contributors 2024.
+
+//! TBF objects, mock policies, digests and clients shared by the process
+//! checker tests.
+
+extern crate std;
+
+use core::cell::Cell;
+use kernel::hil::digest::{ClientData, ClientDataHash, ClientDataVerify, ClientHash, ClientVerify};
+use kernel::hil::digest::{DigestData, DigestDataHash, DigestDataVerify, DigestHash};
+use kernel::hil::digest::{DigestVerify, Sha256};
+use kernel::process::{ProcessBinary, ProcessCheckerMachine, ProcessCheckerMachineClient};
+use kernel::process_checker::{AppCredentialsPolicy, AppCredentialsPolicyClient};
+use kernel::process_checker::{CheckResult, ProcessCheckError};
+use kernel::utilities::cells::OptionalCell;
+use kernel::utilities::leasable_buffer::{SubSlice, SubSliceMut};
+use kernel::ErrorCode;
+use std::boxed::Box;
+use tock_tbf::types::{TbfFooterV2Credentials, TbfFooterV2CredentialsType};
+
+pub(crate) fn leak<T>(t: T) -> &'static T {
+    Box::leak(Box::new(t))
+}
+
+/// A zeroed buffer for a digest, hash or signature.
+pub(crate) fn buffer<const L: usize>() -> &'static mut [u8; L] {
+    Box::leak(Box::new([0; L]))
+}
+
+/// Build an enabled TBF header with a main header and the four byte
+/// package name `name`.
+pub(crate) const fn tbf_header(name: &[u8; 4]) -> [u8; 40] {
+    let mut header = [0; 40];
+    let words: [u32; 10] = [
+        // Version 2, header size 40.
+        2 | (40 << 16),
+        // Total size.
+        0x100,
+        // Enabled.
+        1,
+        // Checksum, filled in below.
+        0,
+        // Main TLV with all fields zero.
+        1 | (12 << 16),
+        0,
+        0,
+        0,
+        // Package name TLV.
+        3 | (4 << 16),
+        u32::from_le_bytes(*name),
+    ];
+    let mut checksum = 0;
+    let mut i = 0;
+    while i < words.len() {
+        checksum ^= words[i];
+        i += 1;
+    }
+    let mut i = 0;
+    while i < words.len() {
+        let bytes = if i == 3 {
+            checksum.to_le_bytes()
+        } else {
+            words[i].to_le_bytes()
+        };
+        header[i * 4] = bytes[0];
+        header[i * 4 + 1] = bytes[1];
+        header[i * 4 + 2] = bytes[2];
+        header[i * 4 + 3] = bytes[3];
+        i += 1;
+    }
+    header
+}
+
+/// A 0x100 byte TBF object with only the header from `tbf_header()`.
+pub(crate) const fn tbf_object(name: &[u8; 4]) -> [u8; 0x100] {
+    concat(&[&tbf_header(name)])
+}
+
+/// Credential data as parsed from a footer: the credential type followed by
+/// `fill` bytes.
+pub(crate) const fn credentials<const N: usize>(
+    format: TbfFooterV2CredentialsType,
+    fill: u8,
+) -> [u8; N] {
+    let mut credentials = [fill; N];
+    credentials[0] = format as u8;
+    credentials[1] = 0;
+    credentials[2] = 0;
+    credentials[3] = 0;
+    credentials
+}
+
+/// A credentials footer TLV of `N` bytes with zeroed credential data.
+pub(crate) const fn footer<const N: usize>(format: TbfFooterV2CredentialsType) -> [u8; N] {
+    let mut footer = [0; N];
+    // TbfHeaderTypes::TbfFooterCredentials
+    footer[0] = 128;
+    footer[2] = (N - 4) as u8;
+    footer[3] = ((N - 4) >> 8) as u8;
+    footer[4] = format as u8;
+    footer
+}
+
+/// Concatenate `parts` into an `N` byte array, padding with zeros.
+pub(crate) const fn concat<const N: usize>(parts: &[&[u8]]) -> [u8; N] {
+    let mut out = [0; N];
+    let mut offset = 0;
+    let mut part = 0;
+    while part < parts.len() {
+        let mut i = 0;
+        while i < parts[part].len() {
+            out[offset + i] = parts[part][i];
+            i += 1;
+        }
+        offset += parts[part].len();
+        part += 1;
+    }
+    out
+}
+
+/// Stand-in for a digest: XORs the data into `L` bytes.
+pub(crate) const fn fold<const L: usize>(data: &[u8]) -> [u8; L] {
+    let mut digest = [0; L];
+    let mut i = 0;
+    while i < data.len() {
+        digest[i % L] ^= data[i];
+        i += 1;
+    }
+    digest
+}
+
+/// Digest engine computing `fold()` over the added data that completes
+/// synchronously. `rewritten` is XORed into the first byte of the data to
+/// emulate flash whose contents changed in place.
+pub(crate) struct MockDigest<const L: usize> {
+    pub(crate) rewritten: Cell<u8>,
+    digest: Cell<[u8; L]>,
+    hash_client: OptionalCell<&'static dyn ClientDataHash<L>>,
+    verify_client: OptionalCell<&'static dyn ClientDataVerify<L>>,
+}
+
+impl<const L: usize> MockDigest<L> {
+    pub(crate) fn new() -> &'static Self {
+        leak(Self {
+            rewritten: Cell::new(0),
+            digest: Cell::new([0; L]),
+            hash_client: OptionalCell::empty(),
+            verify_client: OptionalCell::empty(),
+        })
+    }
+}
+
+impl<const L: usize> DigestData<'static, L> for MockDigest<L> {
+    fn set_data_client(&'static self, _client: &'static dyn ClientData<L>) {}
+
+    fn add_data(
+        &self,
+        data: SubSlice<'static, u8>,
+    ) -> Result<(), (ErrorCode, SubSlice<'static, u8>)> {
+        let mut digest = self.digest.get();
+        digest[0] ^= self.rewritten.get();
+        for (i, byte) in fold::<L>(data.as_slice()).iter().enumerate() {
+            digest[i] ^= byte;
+        }
+        self.digest.set(digest);
+        let data = data.take();
+        self.hash_client
+            .map(|c| c.add_data_done(Ok(()), SubSlice::new(data)));
+        self.verify_client
+            .map(|c| c.add_data_done(Ok(()), SubSlice::new(data)));
+        Ok(())
+    }
+
+    fn add_mut_data(
+        &self,
+        data: SubSliceMut<'static, u8>,
+    ) -> Result<(), (ErrorCode, SubSliceMut<'static, u8>)> {
+        Err((ErrorCode::NOSUPPORT, data))
+    }
+
+    fn clear_data(&self) {
+        self.digest.set([0; L]);
+    }
+}
+
+impl<const L: usize> DigestHash<'static, L> for MockDigest<L> {
+    fn set_hash_client(&'static self, _client: &'static dyn ClientHash<L>) {}
+
+    fn run(
+        &'static self,
+        digest: &'static mut [u8; L],
+    ) -> Result<(), (ErrorCode, &'static mut [u8; L])> {
+        *digest = self.digest.get();
+        self.hash_client.map(|c| c.hash_done(Ok(()), digest));
+        Ok(())
+    }
+}
+
+impl<const L: usize> DigestVerify<'static, L> for MockDigest<L> {
+    fn set_verify_client(&'static self, _client: &'static dyn ClientVerify<L>) {}
+
+    fn verify(
+        &'static self,
+        compare: &'static mut [u8; L],
+    ) -> Result<(), (ErrorCode, &'static mut [u8; L])> {
+        let matches = self.digest.get() == *compare;
+        self.verify_client
+            .map(|c| c.verification_done(Ok(matches), compare));
+        Ok(())
+    }
+}
+
+impl<const L: usize> DigestDataHash<'static, L> for MockDigest<L> {
+    fn set_client(&'static self, client: &'static dyn ClientDataHash<L>) {
+        self.hash_client.set(client);
+    }
+}
+
+impl<const L: usize> DigestDataVerify<'static, L> for MockDigest<L> {
+    fn set_client(&'static self, client: &'static dyn ClientDataVerify<L>) {
+        self.verify_client.set(client);
+    }
+}
+
+impl Sha256 for MockDigest<32> {
+    fn set_mode_sha256(&self) -> Result<(), ErrorCode> {
+        Ok(())
+    }
+}
+
+/// Policy that accepts credentials of type `accept`, rejects credentials of
+/// type `reject`, and does not support any others.
+///
+/// Checks complete synchronously unless `deferred` is set, in which case they
+/// complete when `complete()` is called. `checks` counts the checks started.
+pub(crate) struct MockPolicy {
+    pub(crate) deferred: Cell<bool>,
+    pub(crate) checks: Cell<usize>,
+    accept: TbfFooterV2CredentialsType,
+    reject: Option<TbfFooterV2CredentialsType>,
+    pending: OptionalCell<(CheckResult, TbfFooterV2Credentials, &'static [u8])>,
+    client: OptionalCell<&'static dyn AppCredentialsPolicyClient<'static>>,
+}
+
+impl MockPolicy {
+    pub(crate) fn new(
+        accept: TbfFooterV2CredentialsType,
+        reject: Option<TbfFooterV2CredentialsType>,
+    ) -> &'static Self {
+        leak(Self {
+            deferred: Cell::new(false),
+            checks: Cell::new(0),
+            accept,
+            reject,
+            pending: OptionalCell::empty(),
+            client: OptionalCell::empty(),
+        })
+    }
+
+    /// Finish a deferred check.
+    pub(crate) fn complete(&self) {
+        if let Some((result, credentials, binary)) = self.pending.take() {
+            self.client
+                .map(|client| client.check_done(Ok(result), credentials, binary));
+        }
+    }
+}
+
+impl AppCredentialsPolicy<'static> for MockPolicy {
+    fn require_credentials(&self) -> bool {
+        true
+    }
+
+    fn check_credentials(
+        &self,
+        credentials: TbfFooterV2Credentials,
+        binary: &'static [u8],
+    ) -> Result<(), (ErrorCode, TbfFooterV2Credentials, &'static [u8])> {
+        let result = if credentials.format() == self.accept {
+            CheckResult::Accept
+        } else if Some(credentials.format()) == self.reject {
+            CheckResult::Reject
+        } else {
+            return Err((ErrorCode::NOSUPPORT, credentials, binary));
+        };
+        self.checks.set(self.checks.get() + 1);
+        self.pending.set((result, credentials, binary));
+        if !self.deferred.get() {
+            self.complete();
+        }
+        Ok(())
+    }
+
+    fn set_client(&self, client: &'static dyn AppCredentialsPolicyClient<'static>) {
+        self.client.set(client);
+    }
+
+    fn cancel(&self) {
+        self.pending.clear();
+    }
+}
+
+/// Records the results of a policy.
+pub(crate) struct RecordingClient {
+    /// Number of results received.
+    pub(crate) results: Cell<usize>,
+    /// The last result received.
+    pub(crate) result: Cell<Option<Result<CheckResult, ErrorCode>>>,
+}
+
+impl RecordingClient {
+    pub(crate) fn new() -> &'static Self {
+        leak(Self {
+            results: Cell::new(0),
+            result: Cell::new(None),
+        })
+    }
+}
+
+impl AppCredentialsPolicyClient<'static> for RecordingClient {
+    fn check_done(
+        &self,
+        result: Result<CheckResult, ErrorCode>,
+        _credentials: TbfFooterV2Credentials,
+        _binary: &'static [u8],
+    ) {
+        self.results.set(self.results.get() + 1);
+        self.result.set(Some(result));
+    }
+}
+
+/// Outcome of checking a process binary with a `ProcessCheckerMachine`:
+/// `Ok(())` if accepted, otherwise the index of the rejected credential, or
+/// `None` if no credential was accepted.
+pub(crate) type Outcome = Result<(), Option<u32>>;
+
+/// Records the outcome of a `ProcessCheckerMachine` check.
+pub(crate) struct MachineClient {
+    pub(crate) outcome: Cell<Option<Outcome>>,
+}
+
+impl ProcessCheckerMachineClient for MachineClient {
+    fn done(&self, _process_binary: ProcessBinary, result: Result<(), ProcessCheckError>) {
+        self.outcome.set(Some(match result {
+            Ok(()) => Ok(()),
+            Err(ProcessCheckError::CredentialsRejected(index)) => Err(Some(index)),
+            Err(ProcessCheckError::CredentialsNotAccepted) => Err(None),
+            Err(ProcessCheckError::InternalError) => panic!("internal error"),
+        }));
+    }
+}
+
+/// TBF object checked by `check()`.
+pub(crate) static APP: [u8; 0x100] = tbf_object(b"test");
+
+/// Start checking `APP` with the footer region `footers` using `policy`.
+pub(crate) fn start_check<P: AppCredentialsPolicy<'static>>(
+    policy: &'static P,
+    footers: &'static [u8],
+) -> &'static MachineClient {
+    let machine = leak(ProcessCheckerMachine::new(policy));
+    let client = leak(MachineClient {
+        outcome: Cell::new(None),
+    });
+    policy.set_client(machine);
+    machine.set_client(client);
+
+    let process_binary = ProcessBinary {
+        flash: &APP,
+        footers,
+        header: tock_tbf::parse::parse_tbf_header(&APP, 2).unwrap(),
+    };
+    assert!(machine.check(process_binary).is_ok());
+    client
+}
+
+/// Check `APP` with the footer region `footers` using a `policy` that
+/// completes checks synchronously, and return the outcome if the check
+/// finished.
+pub(crate) fn check<P: AppCredentialsPolicy<'static>>(
+    policy: &'static P,
+    footers: &'static [u8],
+) -> Option<Outcome> {
+    start_check(policy, footers).outcome.get()
+}
@@ -0,0 +1,48 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright Tock Contributors 2024.
+
+//! Alarm-based timeout for process credential checks.
+
+use kernel::hil::time::{Alarm, AlarmClient, ConvertTicks};
+use kernel::process_checker::{CheckTimeout, CheckTimeoutClient};
+use kernel::utilities::cells::OptionalCell;
+
+/// A [`CheckTimeout`] that expires `timeout_ms` milliseconds after it is
+/// started.
+pub struct AlarmCheckTimeout<'a, A: Alarm<'a>> {
+    alarm: &'a A,
+    timeout_ms: u32,
+    client: OptionalCell<&'static dyn CheckTimeoutClient>,
+}
+
+impl<'a, A: Alarm<'a>> AlarmCheckTimeout<'a, A> {
+    pub fn new(alarm: &'a A, timeout_ms: u32) -> Self {
+        Self {
+            alarm,
+            timeout_ms,
+            client: OptionalCell::empty(),
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>> CheckTimeout for AlarmCheckTimeout<'a, A> {
+    fn set_client(&self, client: &'static dyn CheckTimeoutClient) {
+        self.client.set(client);
+    }
+
+    fn start(&self) {
+        self.alarm
+            .set_alarm(self.alarm.now(), self.alarm.ticks_from_ms(self.timeout_ms));
+    }
+
+    fn cancel(&self) {
+        let _ = self.alarm.disarm();
+    }
+}
+
+impl<'a, A: Alarm<'a>> AlarmClient for AlarmCheckTimeout<'a, A> {
+    fn alarm(&self) {
+        self.client.map(|client| client.timeout_expired());
+    }
+}
@@ -31,6 +31,8 @@
 //! experiments on generated Tock code have confirmed this zero cost in
 //! practice.
 
+use crate::process_binary::KernelVersionPolicy;
+
 /// Data structure holding compile-time configuration options.
 ///
 /// To change the configuration, modify the relevant values in the `CONFIG`
@@ -80,8 +82,34 @@ pub(crate) struct Config {
     // credentials checking, e.g., whether elf2tab and tockloader are generating
     // properly formatted footers.
     pub(crate) debug_process_credentials: bool,
+    /// The largest footer region, in bytes, the kernel accepts for a process
+    /// binary.
+    ///
+    /// The footer region is the part of a TBF object after the end of the
+    /// application binary and is used to store credentials. Because the TBF
+    /// total size is commonly padded to satisfy MPU alignment, large footer
+    /// regions are legitimate and there is no limit by default. Boards that
+    /// know their footers are small can enable the `limit_footer_size` feature
+    /// to reject process binaries with more than `LIMITED_FOOTER_SIZE` bytes
+    /// of footers.
+    pub(crate) max_footer_size: usize,
+
+    /// How the kernel handles the `KernelVersion` TBF header of a process.
+    ///
+    /// By default a process that requires a newer kernel, or that has no
+    /// `KernelVersion` header, is not loaded. Boards in development can enable
+    /// the `kernel_version_warn_mismatch` feature to load such processes with a
+    /// warning, as long as they require the running kernel's major version.
+    /// The `kernel_version_ignore` feature skips the check entirely and takes
+    /// precedence over `kernel_version_warn_mismatch`.
+    pub(crate) kernel_version_policy: KernelVersionPolicy,
 }
 
+/// Largest footer region, in bytes, accepted with the `limit_footer_size`
+/// feature. This leaves room for several credentials, including RSA-4096
+/// signatures.
+const LIMITED_FOOTER_SIZE: usize = 4096;
+
 /// A unique instance of `Config` where compile-time configuration options are
 /// defined. These options are available in the kernel crate to be used for
 /// relevant configuration. Notably, this is the only location in the Tock
@@ -92,4 +120,16 @@ pub(crate) const CONFIG: Config = Config {
     debug_load_processes: cfg!(feature = "debug_load_processes"),
     debug_panics: !cfg!(feature = "no_debug_panics"),
     debug_process_credentials: cfg!(feature = "debug_process_credentials"),
+    max_footer_size: if cfg!(feature = "limit_footer_size") {
+        LIMITED_FOOTER_SIZE
+    } else {
+        usize::MAX
+    },
+    kernel_version_policy: if cfg!(feature = "kernel_version_ignore") {
+        KernelVersionPolicy::Ignore
+    } else if cfg!(feature = "kernel_version_warn_mismatch") {
+        KernelVersionPolicy::WarnMismatch
+    } else {
+        KernelVersionPolicy::Strict
+    },
 };
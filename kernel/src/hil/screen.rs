@@ -217,6 +217,21 @@ pub trait Screen<'a> {
     /// without requesting it from the screen.
     fn get_pixel_format(&self) -> ScreenPixelFormat;
 
+    /// Get all of the pixel formats the screen can use.
+    ///
+    /// Screens that can switch between several formats override this so
+    /// clients can pick a format both sides accept. The default returns only
+    /// the current pixel format.
+    fn supported_pixel_formats(&self) -> &'static [ScreenPixelFormat] {
+        match self.get_pixel_format() {
+            ScreenPixelFormat::Mono => &[ScreenPixelFormat::Mono],
+            ScreenPixelFormat::RGB_233 => &[ScreenPixelFormat::RGB_233],
+            ScreenPixelFormat::RGB_565 => &[ScreenPixelFormat::RGB_565],
+            ScreenPixelFormat::RGB_888 => &[ScreenPixelFormat::RGB_888],
+            ScreenPixelFormat::ARGB_8888 => &[ScreenPixelFormat::ARGB_8888],
+        }
+    }
+
     /// Get the current rotation.
     ///
     /// This function is synchronous as the driver should know this value
@@ -328,4 +343,10 @@ pub trait ScreenClient {
     /// Some screens need some time to start, this function is called when the
     /// screen is ready.
     fn screen_is_ready(&self);
+
+    /// Screens whose resolution can change at runtime (for example a virtual
+    /// display whose window was resized by the host) call this function with
+    /// the new resolution. Clients should query `get_resolution()` again and
+    /// redraw. The default implementation ignores the change.
+    fn resolution_changed(&self, _width: usize, _height: usize) {}
 }
@@ -12,6 +12,24 @@ use core::fmt;
 use crate::config;
 use crate::debug;
 
+/// How `ProcessBinary::create()` handles the `KernelVersion` TBF header.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum KernelVersionPolicy {
+    /// Reject processes that require an incompatible kernel version or that
+    /// do not include a `KernelVersion` header.
+    #[default]
+    Strict,
+
+    /// Load processes that require a newer minor version of the running
+    /// kernel's major version, or that do not include a `KernelVersion`
+    /// header, but print a warning. Processes that require a different major
+    /// version are still rejected.
+    WarnMismatch,
+
+    /// Do not check the `KernelVersion` header.
+    Ignore,
+}
+
 /// Errors resulting from trying to load a process binary structure from flash.
 pub enum ProcessBinaryError {
     /// No TBF header was found.
@@ -50,6 +68,26 @@ pub enum ProcessBinaryError {
 
     /// This entry in flash is just padding.
     Padding,
+
+    /// The TBF header places the end of the application binary inside the
+    /// header itself, so the header is malformed.
+    ///
+    /// `binary_end` is the offset of the end of the binary and `header_length`
+    /// is the length of the TBF header, both from the start of the TBF object.
+    BinaryEndInsideHeader {
+        binary_end: usize,
+        header_length: usize,
+    },
+
+    /// The footer region after the application binary is larger than the
+    /// kernel is configured to accept.
+    ///
+    /// `footer_size` is the length of the footer region in bytes and
+    /// `max_footer_size` is the configured limit.
+    FooterRegionTooLarge {
+        footer_size: usize,
+        max_footer_size: usize,
+    },
 }
 
 impl From<tock_tbf::types::TbfParseError> for ProcessBinaryError {
@@ -62,6 +100,23 @@ impl From<tock_tbf::types::TbfParseError> for ProcessBinaryError {
     }
 }
 
+impl ProcessBinaryError {
+    /// For an `IncorrectFlashAddress` error, return how far the binary is from
+    /// where it was linked to run, in bytes. A positive value means the binary
+    /// was placed at a higher address than expected.
+    ///
+    /// Returns `None` for all other errors.
+    pub fn flash_address_delta(&self) -> Option<i64> {
+        match *self {
+            ProcessBinaryError::IncorrectFlashAddress {
+                actual_address,
+                expected_address,
+            } => Some(actual_address as i64 - expected_address as i64),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Debug for ProcessBinaryError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -81,11 +136,24 @@ impl fmt::Debug for ProcessBinaryError {
             ProcessBinaryError::IncorrectFlashAddress {
                 actual_address,
                 expected_address,
-            } => write!(
-                f,
-                "App flash does not match requested address. Actual:{:#x}, Expected:{:#x}",
-                actual_address, expected_address
-            ),
+            } => {
+                write!(
+                    f,
+                    "App flash does not match requested address. Actual:{:#x}, Expected:{:#x}",
+                    actual_address, expected_address
+                )?;
+                let delta = self.flash_address_delta().unwrap_or(0);
+                if delta < 0 {
+                    write!(f, " ({:#x} bytes too low)", -delta)?;
+                } else {
+                    write!(f, " ({:#x} bytes too high)", delta)?;
+                }
+                write!(
+                    f,
+                    ". Relink the app for {:#x} or flash it at {:#x}",
+                    actual_address, expected_address
+                )
+            }
 
             ProcessBinaryError::IncompatibleKernelVersion { version } => match version {
                 Some((major, minor)) => write!(
@@ -106,6 +174,24 @@ impl fmt::Debug for ProcessBinaryError {
             ProcessBinaryError::Padding => {
                 write!(f, "Process item is just padding")
             }
+
+            ProcessBinaryError::BinaryEndInsideHeader {
+                binary_end,
+                header_length,
+            } => write!(
+                f,
+                "Binary end {:#x} is inside the TBF header of length {:#x}",
+                binary_end, header_length
+            ),
+
+            ProcessBinaryError::FooterRegionTooLarge {
+                footer_size,
+                max_footer_size,
+            } => write!(
+                f,
+                "Footer region of {} bytes exceeds the maximum of {} bytes",
+                footer_size, max_footer_size
+            ),
         }
     }
 }
@@ -130,7 +216,8 @@ impl ProcessBinary {
         app_flash: &'static [u8],
         header_length: usize,
         tbf_version: u16,
-        require_kernel_version: bool,
+        version_policy: KernelVersionPolicy,
+        max_footer_size: usize,
     ) -> Result<Self, ProcessBinaryError> {
         // Get a slice for just the app header.
         let header_flash = app_flash
@@ -168,50 +255,37 @@ impl ProcessBinary {
             return Err(ProcessBinaryError::NotEnabledProcess);
         }
 
-        if let Some((major, minor)) = tbf_header.get_kernel_version() {
-            // If the `KernelVersion` header is present, we read the requested
-            // kernel version and compare it to the running kernel version.
-            if crate::KERNEL_MAJOR_VERSION != major || crate::KERNEL_MINOR_VERSION < minor {
-                // If the kernel major version is different, we prevent the
-                // process from being loaded.
-                //
-                // If the kernel major version is the same, we compare the
-                // kernel minor version. The current running kernel minor
-                // version has to be greater or equal to the one that the
-                // process has requested. If not, we prevent the process from
-                // loading.
-                if config::CONFIG.debug_load_processes {
-                    debug!(
-                        "WARN process {} requires kernel>={}.{} and <{}.0, (running kernel {}.{})",
-                        tbf_header.get_package_name().unwrap_or(""),
-                        major,
-                        minor,
-                        (major + 1),
-                        crate::KERNEL_MAJOR_VERSION,
-                        crate::KERNEL_MINOR_VERSION
-                    );
-                }
-                return Err(ProcessBinaryError::IncompatibleKernelVersion {
-                    version: Some((major, minor)),
-                });
-            }
-        } else if require_kernel_version {
-            // If enforcing the kernel version is requested, and the
-            // `KernelVersion` header is not present, we prevent the process
-            // from loading.
+        if let Some(mismatch) = check_kernel_version(
+            tbf_header.get_kernel_version(),
+            version_policy,
+            tbf_header.get_package_name().unwrap_or(""),
+        )? {
+            // The policy allows loading the process anyway, but make sure the
+            // mismatch is visible.
+            debug!(
+                "WARN loading process {} anyway: {:?}",
+                tbf_header.get_package_name().unwrap_or(""),
+                mismatch
+            );
+        }
+
+        let binary_end = tbf_header.get_binary_end() as usize;
+        let total_size = app_flash.len();
+
+        // Make sure the footer region described by the header is sane before
+        // we try to use it.
+        if let Err(e) = check_footer_region(header_length, binary_end, total_size, max_footer_size)
+        {
             if config::CONFIG.debug_load_processes {
                 debug!(
-                    "WARN process {} has no kernel version header",
-                    tbf_header.get_package_name().unwrap_or("")
+                    "Invalid footer region for process {}: {:?}",
+                    tbf_header.get_package_name().unwrap_or(""),
+                    e
                 );
-                debug!("Please upgrade to elf2tab >= 0.8.0");
             }
-            return Err(ProcessBinaryError::IncompatibleKernelVersion { version: None });
+            return Err(e);
         }
 
-        let binary_end = tbf_header.get_binary_end() as usize;
-        let total_size = app_flash.len();
-
         // End of the portion of the application binary covered by integrity.
         // Now handle footers.
         let footer_region = app_flash
@@ -247,3 +321,332 @@ impl ProcessBinary {
         }
     }
 }
+
+/// Check the kernel version a process requires against the running kernel.
+///
+/// `version` is the `(major, minor)` version from the process's
+/// `KernelVersion` TBF header, or `None` if the header is missing. A process is
+/// compatible if the major versions match and the running minor version is at
+/// least the requested one. `policy` decides what happens otherwise.
+///
+/// Returns `Ok(Some(error))` if the process is incompatible but `policy`
+/// allows loading it anyway, so the caller can warn about `error`.
+fn check_kernel_version(
+    version: Option<(u16, u16)>,
+    policy: KernelVersionPolicy,
+    package_name: &str,
+) -> Result<Option<ProcessBinaryError>, ProcessBinaryError> {
+    if policy == KernelVersionPolicy::Ignore {
+        return Ok(None);
+    }
+
+    if let Some((major, minor)) = version {
+        // If the `KernelVersion` header is present, we read the requested
+        // kernel version and compare it to the running kernel version.
+        if crate::KERNEL_MAJOR_VERSION != major || crate::KERNEL_MINOR_VERSION < minor {
+            let error = ProcessBinaryError::IncompatibleKernelVersion {
+                version: Some((major, minor)),
+            };
+
+            // If the kernel major version is the same, a policy can allow a
+            // process that requested a newer minor version.
+            if crate::KERNEL_MAJOR_VERSION == major && policy == KernelVersionPolicy::WarnMismatch {
+                return Ok(Some(error));
+            }
+
+            // Otherwise, if the kernel major version is different, we prevent
+            // the process from being loaded.
+            //
+            // If the kernel major version is the same, we compare the kernel
+            // minor version. The current running kernel minor version has to
+            // be greater or equal to the one that the process has requested.
+            // If not, we prevent the process from loading.
+            if config::CONFIG.debug_load_processes {
+                debug!(
+                    "WARN process {} requires kernel>={}.{} and <{}.0, (running kernel {}.{})",
+                    package_name,
+                    major,
+                    minor,
+                    (major + 1),
+                    crate::KERNEL_MAJOR_VERSION,
+                    crate::KERNEL_MINOR_VERSION
+                );
+            }
+            return Err(error);
+        }
+    } else {
+        // The `KernelVersion` header is not present.
+        if policy == KernelVersionPolicy::WarnMismatch {
+            return Ok(Some(ProcessBinaryError::IncompatibleKernelVersion {
+                version: None,
+            }));
+        }
+        if config::CONFIG.debug_load_processes {
+            debug!("WARN process {} has no kernel version header", package_name);
+            debug!("Please upgrade to elf2tab >= 0.8.0");
+        }
+        return Err(ProcessBinaryError::IncompatibleKernelVersion { version: None });
+    }
+
+    Ok(None)
+}
+
+/// Check that the footer region of a TBF object is well formed.
+///
+/// The application binary must end at or after the end of the TBF header, and
+/// the footer region (`binary_end..total_size`) must be no larger than
+/// `max_footer_size`. A `binary_end` past `total_size` is reported as
+/// `NotEnoughFlash`.
+fn check_footer_region(
+    header_length: usize,
+    binary_end: usize,
+    total_size: usize,
+    max_footer_size: usize,
+) -> Result<(), ProcessBinaryError> {
+    if binary_end < header_length {
+        return Err(ProcessBinaryError::BinaryEndInsideHeader {
+            binary_end,
+            header_length,
+        });
+    }
+
+    let footer_size = total_size
+        .checked_sub(binary_end)
+        .ok_or(ProcessBinaryError::NotEnoughFlash)?;
+    if footer_size > max_footer_size {
+        return Err(ProcessBinaryError::FooterRegionTooLarge {
+            footer_size,
+            max_footer_size,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::{
+        check_footer_region, check_kernel_version, KernelVersionPolicy, ProcessBinary,
+        ProcessBinaryError,
+    };
+
+    /// Build a 0x100 byte TBF object with a base header using `flags`,
+    /// followed by the TLVs in `tlvs`.
+    pub(crate) const fn tbf_object(flags: u32, tlvs: &[u8]) -> [u8; 0x100] {
+        let mut flash = [0; 0x100];
+        let header_size = 16 + tlvs.len();
+        let words: [u32; 3] = [2 | ((header_size as u32) << 16), 0x100, flags];
+        let mut checksum = 0;
+        let mut i = 0;
+        while i < words.len() {
+            let bytes = words[i].to_le_bytes();
+            flash[i * 4] = bytes[0];
+            flash[i * 4 + 1] = bytes[1];
+            flash[i * 4 + 2] = bytes[2];
+            flash[i * 4 + 3] = bytes[3];
+            checksum ^= words[i];
+            i += 1;
+        }
+        let mut i = 0;
+        while i < tlvs.len() {
+            flash[16 + i] = tlvs[i];
+            if i % 4 == 3 {
+                checksum ^= u32::from_le_bytes([tlvs[i - 3], tlvs[i - 2], tlvs[i - 1], tlvs[i]]);
+            }
+            i += 1;
+        }
+        let bytes = u32::to_le_bytes(checksum);
+        flash[12] = bytes[0];
+        flash[13] = bytes[1];
+        flash[14] = bytes[2];
+        flash[15] = bytes[3];
+        flash
+    }
+
+    /// Main TLV with all fields zero.
+    const MAIN: [u8; 16] = [1, 0, 12, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    /// Program TLV placing the end of the binary at 0x200, past the end of
+    /// the TBF object.
+    const PROGRAM_PAST_END: [u8; 24] = [
+        9, 0, 20, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x02, 0, 0, 0, 0, 0, 0,
+    ];
+
+    /// TBF header with only the base header: padding of 0x100 bytes.
+    static PADDING: [u8; 0x100] = tbf_object(0, &[]);
+
+    /// TBF header with a main header but the enabled flag cleared.
+    static DISABLED_APP: [u8; 0x100] = tbf_object(0, &MAIN);
+
+    /// Enabled app whose binary fills the whole TBF object.
+    static APP: [u8; 0x100] = tbf_object(1, &MAIN);
+
+    /// Enabled app whose binary ends past the end of the TBF object.
+    static APP_PAST_END: [u8; 0x100] = tbf_object(1, &PROGRAM_PAST_END);
+
+    /// Program TLV placing the end of the binary at 0x40, leaving 0xc0 bytes
+    /// of footers.
+    pub(crate) const PROGRAM_WITH_FOOTERS: [u8; 24] = [
+        9, 0, 20, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x40, 0, 0, 0, 0, 0, 0, 0,
+    ];
+
+    /// Enabled app with a 0xc0 byte footer region.
+    static APP_WITH_FOOTERS: [u8; 0x100] = tbf_object(1, &PROGRAM_WITH_FOOTERS);
+
+    #[test]
+    fn test_flash_address_delta() {
+        let err = ProcessBinaryError::IncorrectFlashAddress {
+            actual_address: 0x40000,
+            expected_address: 0x30000,
+        };
+        assert_eq!(err.flash_address_delta(), Some(0x10000));
+
+        let err = ProcessBinaryError::IncorrectFlashAddress {
+            actual_address: 0x30000,
+            expected_address: 0x40000,
+        };
+        assert_eq!(err.flash_address_delta(), Some(-0x10000));
+
+        assert_eq!(ProcessBinaryError::Padding.flash_address_delta(), None);
+    }
+
+    #[test]
+    fn test_padding_is_distinct_from_disabled() {
+        let res = ProcessBinary::create(&PADDING, 16, 2, KernelVersionPolicy::Strict, usize::MAX);
+        assert!(matches!(res, Err(ProcessBinaryError::Padding)));
+
+        let res = ProcessBinary::create(
+            &DISABLED_APP,
+            32,
+            2,
+            KernelVersionPolicy::Strict,
+            usize::MAX,
+        );
+        assert!(matches!(res, Err(ProcessBinaryError::NotEnabledProcess)));
+    }
+
+    #[test]
+    fn test_create_binary_end_at_total_size() {
+        let pb =
+            ProcessBinary::create(&APP, 32, 2, KernelVersionPolicy::Ignore, usize::MAX).unwrap();
+        assert!(pb.footers.is_empty());
+        assert_eq!(pb.get_integrity_region_slice().len(), APP.len());
+    }
+
+    #[test]
+    fn test_create_binary_end_past_total_size() {
+        let res = ProcessBinary::create(
+            &APP_PAST_END,
+            40,
+            2,
+            KernelVersionPolicy::Ignore,
+            usize::MAX,
+        );
+        assert!(matches!(res, Err(ProcessBinaryError::NotEnoughFlash)));
+    }
+
+    #[test]
+    fn test_create_footer_region_limit() {
+        let pb = ProcessBinary::create(&APP_WITH_FOOTERS, 40, 2, KernelVersionPolicy::Ignore, 0xc0)
+            .unwrap();
+        assert_eq!(pb.footers.len(), 0xc0);
+
+        let res =
+            ProcessBinary::create(&APP_WITH_FOOTERS, 40, 2, KernelVersionPolicy::Ignore, 0xbf);
+        assert!(matches!(
+            res,
+            Err(ProcessBinaryError::FooterRegionTooLarge {
+                footer_size: 0xc0,
+                max_footer_size: 0xbf,
+            })
+        ));
+    }
+
+    #[test]
+    fn test_footer_region_valid() {
+        assert!(check_footer_region(0x40, 0x400, 0x800, 0x400).is_ok());
+        assert!(check_footer_region(0x40, 0x40, 0x40, 0).is_ok());
+    }
+
+    #[test]
+    fn test_footer_region_too_large() {
+        let res = check_footer_region(0x40, 0x100, 0x10000, 0x1000);
+        assert!(matches!(
+            res,
+            Err(ProcessBinaryError::FooterRegionTooLarge {
+                footer_size: 0xff00,
+                max_footer_size: 0x1000,
+            })
+        ));
+    }
+
+    #[test]
+    fn test_binary_end_inside_header() {
+        let res = check_footer_region(0x40, 0x20, 0x800, usize::MAX);
+        assert!(matches!(
+            res,
+            Err(ProcessBinaryError::BinaryEndInsideHeader {
+                binary_end: 0x20,
+                header_length: 0x40,
+            })
+        ));
+    }
+
+    #[test]
+    fn test_binary_end_past_total_size() {
+        let res = check_footer_region(0x40, 0x900, 0x800, usize::MAX);
+        assert!(matches!(res, Err(ProcessBinaryError::NotEnoughFlash)));
+    }
+
+    #[test]
+    fn test_kernel_version_policy() {
+        let major = crate::KERNEL_MAJOR_VERSION;
+        let minor = crate::KERNEL_MINOR_VERSION;
+        let newer_minor = Some((major, minor + 1));
+        let other_major = Some((major + 1, 0));
+
+        for policy in [
+            KernelVersionPolicy::Strict,
+            KernelVersionPolicy::WarnMismatch,
+            KernelVersionPolicy::Ignore,
+        ] {
+            assert!(matches!(
+                check_kernel_version(Some((major, minor)), policy, ""),
+                Ok(None)
+            ));
+        }
+
+        assert!(matches!(
+            check_kernel_version(newer_minor, KernelVersionPolicy::Strict, ""),
+            Err(ProcessBinaryError::IncompatibleKernelVersion { .. })
+        ));
+        assert!(matches!(
+            check_kernel_version(None, KernelVersionPolicy::Strict, ""),
+            Err(ProcessBinaryError::IncompatibleKernelVersion { version: None })
+        ));
+
+        assert!(matches!(
+            check_kernel_version(newer_minor, KernelVersionPolicy::WarnMismatch, ""),
+            Ok(Some(ProcessBinaryError::IncompatibleKernelVersion { .. }))
+        ));
+        assert!(matches!(
+            check_kernel_version(None, KernelVersionPolicy::WarnMismatch, ""),
+            Ok(Some(ProcessBinaryError::IncompatibleKernelVersion {
+                version: None
+            }))
+        ));
+        assert!(matches!(
+            check_kernel_version(other_major, KernelVersionPolicy::WarnMismatch, ""),
+            Err(ProcessBinaryError::IncompatibleKernelVersion { .. })
+        ));
+
+        assert!(matches!(
+            check_kernel_version(other_major, KernelVersionPolicy::Ignore, ""),
+            Ok(None)
+        ));
+        assert!(matches!(
+            check_kernel_version(None, KernelVersionPolicy::Ignore, ""),
+            Ok(None)
+        ));
+    }
+}
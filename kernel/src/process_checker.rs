@@ -54,9 +54,30 @@ impl fmt::Debug for ProcessCheckError {
     }
 }
 
+impl fmt::Display for ProcessCheckError {
+    /// Human-readable description of the failure. Credentials are numbered as
+    /// in [`ProcessCheckError::CredentialsRejected`], starting from 0 for the
+    /// first footer after the application binary.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProcessCheckError::CredentialsNotAccepted => {
+                write!(f, "no acceptable credentials found")
+            }
+
+            ProcessCheckError::CredentialsRejected(index) => {
+                write!(f, "credential #{} rejected by verifier", index)
+            }
+
+            ProcessCheckError::InternalError => {
+                write!(f, "internal error in the kernel while checking credentials")
+            }
+        }
+    }
+}
+
 /// What a AppCredentialsChecker decided a particular application's credential
 /// indicates about the runnability of an application binary.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CheckResult {
     /// Accept the credential and run the binary.
     Accept,
@@ -67,6 +88,22 @@ pub enum CheckResult {
     Reject,
 }
 
+/// How `ProcessCheckerMachine` combines the results of the footers of a process
+/// binary.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FooterCheckMode {
+    /// Stop at the first footer that is accepted or rejected.
+    #[default]
+    FirstAccept,
+    /// Check every footer and let [`AppCredentialsPolicy::decide`] decide
+    /// based on all of the results. At most [`MAX_CHECKED_FOOTERS`] footers are
+    /// checked.
+    AllFooters,
+}
+
+/// Maximum number of footers checked in [`FooterCheckMode::AllFooters`].
+pub const MAX_CHECKED_FOOTERS: usize = 8;
+
 /// Receives callbacks on whether a credential was accepted or not.
 pub trait AppCredentialsPolicyClient<'a> {
     /// The check for a particular credential is complete. Result of the check
@@ -99,6 +136,35 @@ pub trait AppCredentialsPolicy<'a> {
         credentials: TbfFooterV2Credentials,
         integrity_region: &'a [u8],
     ) -> Result<(), (ErrorCode, TbfFooterV2Credentials, &'a [u8])>;
+
+    /// How the results of the individual footers are combined.
+    fn footer_check_mode(&self) -> FooterCheckMode {
+        FooterCheckMode::FirstAccept
+    }
+
+    /// Decide whether to run a process binary based on the results of all of
+    /// its footers, in footer order. Footers that could not be checked are
+    /// `Pass`.
+    ///
+    /// This is only called in [`FooterCheckMode::AllFooters`]. The default
+    /// returns the first result that is not `Pass`, which matches
+    /// [`FooterCheckMode::FirstAccept`].
+    fn decide(&self, results: &[CheckResult]) -> CheckResult {
+        results
+            .iter()
+            .copied()
+            .find(|result| *result != CheckResult::Pass)
+            .unwrap_or(CheckResult::Pass)
+    }
+
+    /// Stop the credential check in progress, if any.
+    ///
+    /// [`ProcessCheckerMachine`] calls this when a check does not finish before
+    /// its [`CheckTimeout`] expires. Afterwards the policy must accept a new
+    /// `check_credentials()` call. A `check_done()` callback for the cancelled
+    /// check may still arrive and is ignored by the machine. The default does
+    /// nothing, which suits policies that keep no state between calls.
+    fn cancel(&self) {}
 }
 
 /// Whether two processes have the same Application Identifier; two
@@ -175,6 +241,25 @@ pub trait ProcessCheckerMachineClient {
     fn done(&self, process_binary: ProcessBinary, result: Result<(), ProcessCheckError>);
 }
 
+/// Timeout used by [`ProcessCheckerMachine`] to stop waiting for a credential
+/// check that never completes, for example because the hardware performing the
+/// check is wedged.
+pub trait CheckTimeout {
+    fn set_client(&self, client: &'static dyn CheckTimeoutClient);
+
+    /// Start the timeout, restarting it if it is already running.
+    fn start(&self);
+
+    /// Stop the timeout.
+    fn cancel(&self);
+}
+
+/// Client interface for a [`CheckTimeout`].
+pub trait CheckTimeoutClient {
+    /// The timeout expired before it was cancelled.
+    fn timeout_expired(&self);
+}
+
 /// Outcome from checking a single footer credential.
 #[derive(Debug)]
 enum FooterCheckResult {
@@ -201,6 +286,16 @@ pub struct ProcessCheckerMachine {
     process_binary: OptionalCell<ProcessBinary>,
     /// Keep track of which footer is being parsed.
     footer_index: Cell<usize>,
+    /// Whether the policy is checking a footer and has not called
+    /// `check_done()` yet.
+    checking: Cell<bool>,
+    /// Optional timeout for policies that never call `check_done()`.
+    timeout: OptionalCell<&'static dyn CheckTimeout>,
+    /// Results of the footers checked so far, in
+    /// `FooterCheckMode::AllFooters`.
+    results: Cell<[CheckResult; MAX_CHECKED_FOOTERS]>,
+    /// Number of valid entries in `results`.
+    result_count: Cell<usize>,
 }
 
 impl ProcessCheckerMachine {
@@ -210,6 +305,10 @@ impl ProcessCheckerMachine {
             policy: OptionalCell::new(policy),
             process_binary: OptionalCell::empty(),
             client: OptionalCell::empty(),
+            checking: Cell::new(false),
+            timeout: OptionalCell::empty(),
+            results: Cell::new([CheckResult::Pass; MAX_CHECKED_FOOTERS]),
+            result_count: Cell::new(0),
         }
     }
 
@@ -221,15 +320,68 @@ impl ProcessCheckerMachine {
         self.policy.replace(policy);
     }
 
+    /// Give up on a footer if the policy does not finish checking it before
+    /// `timeout` expires.
+    ///
+    /// The policy is then cancelled with `AppCredentialsPolicy::cancel()` so
+    /// it can check the next process binary, and the process binary is
+    /// rejected with `ProcessCheckError::InternalError`. A `check_done()`
+    /// callback arriving after the timeout is ignored.
+    pub fn set_timeout(&'static self, timeout: &'static dyn CheckTimeout) {
+        timeout.set_client(self);
+        self.timeout.set(timeout);
+    }
+
     /// Check this `process_binary` to see if its credentials are valid.
     ///
     /// This must be called from a interrupt callback chain.
     pub fn check(&self, process_binary: ProcessBinary) -> Result<(), ProcessCheckError> {
         self.footer_index.set(0);
+        self.result_count.set(0);
         self.process_binary.set(process_binary);
         self.next()
     }
 
+    fn all_footers(&self) -> bool {
+        self.policy.map_or(false, |policy| {
+            policy.footer_check_mode() == FooterCheckMode::AllFooters
+        })
+    }
+
+    /// Store the result of the current footer for `FooterCheckMode::AllFooters`.
+    fn record(&self, result: CheckResult) {
+        let count = self.result_count.get();
+        if count < MAX_CHECKED_FOOTERS {
+            let mut results = self.results.get();
+            results[count] = result;
+            self.results.set(results);
+            self.result_count.set(count + 1);
+        }
+    }
+
+    /// Let the policy decide based on the results of all footers.
+    fn decide(&self, policy: &'static dyn AppCredentialsPolicy<'static>) {
+        let results = self.results.get();
+        let results = &results[..self.result_count.get()];
+        let result = match policy.decide(results) {
+            CheckResult::Accept => Ok(()),
+            CheckResult::Reject => Err(results
+                .iter()
+                .position(|result| *result == CheckResult::Reject)
+                .map_or(ProcessCheckError::CredentialsNotAccepted, |index| {
+                    ProcessCheckError::CredentialsRejected(index as u32)
+                })),
+            CheckResult::Pass => {
+                if policy.require_credentials() {
+                    Err(ProcessCheckError::CredentialsNotAccepted)
+                } else {
+                    Ok(())
+                }
+            }
+        };
+        self.done(result);
+    }
+
     /// Must be called from a callback context.
     fn next(&self) -> Result<(), ProcessCheckError> {
         let policy = self.policy.get().ok_or(ProcessCheckError::InternalError)?;
@@ -238,13 +390,26 @@ impl ProcessCheckerMachine {
             .take()
             .ok_or(ProcessCheckError::InternalError)?;
         let pb_name = pb.header.get_package_name().unwrap_or("");
+        let integrity_slice = pb.get_integrity_region_slice();
+        let footers = pb.footers;
+
+        // Keep the process binary stored while footers are checked. A policy
+        // may call `check_done()` before `check_credentials()` returns, and
+        // `check_done()` needs the process binary to report the outcome.
+        self.process_binary.set(pb);
 
         // Loop over all footers in the footer region. We don't know how many
         // footers there are, so we use `loop {}`.
+        let all_footers = self.all_footers();
         loop {
             let footer_index = self.footer_index.get();
+            if all_footers && self.result_count.get() == MAX_CHECKED_FOOTERS {
+                self.decide(policy);
+                break;
+            }
 
-            let check_result = ProcessCheckerMachine::check_footer(&pb, policy, footer_index);
+            let check_result =
+                self.check_footer(policy, pb_name, integrity_slice, footers, footer_index);
 
             if config::CONFIG.debug_process_credentials {
                 debug!(
@@ -254,7 +419,15 @@ impl ProcessCheckerMachine {
             }
             match check_result {
                 FooterCheckResult::Checking => {
-                    self.process_binary.set(pb);
+                    // Unless the policy already finished synchronously, wait
+                    // for `check_done()` (or the timeout).
+                    if self.checking.get() {
+                        self.timeout.map(|timeout| timeout.start());
+                    }
+                    break;
+                }
+                FooterCheckResult::PastLastFooter | FooterCheckResult::BadFooter if all_footers => {
+                    self.decide(policy);
                     break;
                 }
                 FooterCheckResult::PastLastFooter | FooterCheckResult::BadFooter => {
@@ -262,26 +435,21 @@ impl ProcessCheckerMachine {
                     // credentials or all credentials were Pass: apply
                     // the checker policy to see if the process
                     // should be allowed to run.
-                    self.policy.map(|policy| {
-                        let requires = policy.require_credentials();
-
-                        let result = if requires {
-                            Err(ProcessCheckError::CredentialsNotAccepted)
-                        } else {
-                            Ok(())
-                        };
-
-                        self.client.map(|client| client.done(pb, result));
-                    });
+                    let result = if policy.require_credentials() {
+                        Err(ProcessCheckError::CredentialsNotAccepted)
+                    } else {
+                        Ok(())
+                    };
+                    self.done(result);
                     break;
                 }
                 FooterCheckResult::FooterNotCheckable => {
                     // Go to next footer
+                    self.record(CheckResult::Pass);
                     self.footer_index.increment();
                 }
                 FooterCheckResult::Error => {
-                    self.client
-                        .map(|client| client.done(pb, Err(ProcessCheckError::InternalError)));
+                    self.done(Err(ProcessCheckError::InternalError));
                     break;
                 }
             }
@@ -289,24 +457,33 @@ impl ProcessCheckerMachine {
         Ok(())
     }
 
+    /// Finish checking the stored process binary and pass it, along with
+    /// `result`, to the client.
+    ///
+    /// The process binary is removed from the machine, so the client
+    /// receives at most one `done()` callback per call to `check()`.
+    fn done(&self, result: Result<(), ProcessCheckError>) {
+        if let Some(pb) = self.process_binary.take() {
+            self.client.map(|client| client.done(pb, result));
+        }
+    }
+
     // Returns whether a footer is being checked or not, and if not, why.
     // Iterates through the footer list until if finds `next_footer` or
     // it reached the end of the footer region.
     fn check_footer(
-        process_binary: &ProcessBinary,
+        &self,
         policy: &'static dyn AppCredentialsPolicy<'static>,
+        pb_name: &str,
+        integrity_slice: &'static [u8],
+        footers: &'static [u8],
         next_footer: usize,
     ) -> FooterCheckResult {
         if config::CONFIG.debug_process_credentials {
-            debug!(
-                "Checking: Checking {:?} footer {}",
-                process_binary.header.get_package_name(),
-                next_footer
-            );
+            debug!("Checking: Checking {} footer {}", pb_name, next_footer);
         }
 
-        let integrity_slice = process_binary.get_integrity_region_slice();
-        let mut footer_slice = process_binary.footers;
+        let mut footer_slice = footers;
 
         if config::CONFIG.debug_process_credentials {
             debug!(
@@ -365,7 +542,14 @@ impl ProcessCheckerMachine {
                         Some(slice) => {
                             footer_slice = slice;
                             if current_footer == next_footer {
-                                match policy.check_credentials(footer, integrity_slice) {
+                                // Set before checking, as the policy may call
+                                // `check_done()` before returning.
+                                self.checking.set(true);
+                                let check = policy.check_credentials(footer, integrity_slice);
+                                if check.is_err() {
+                                    self.checking.set(false);
+                                }
+                                match check {
                                     Ok(()) => {
                                         if config::CONFIG.debug_process_credentials {
                                             debug!("Checking: Found {}, checking", current_footer);
@@ -408,23 +592,55 @@ impl ProcessCheckerMachine {
     }
 }
 
+impl CheckTimeoutClient for ProcessCheckerMachine {
+    fn timeout_expired(&self) {
+        if self.checking.replace(false) {
+            if config::CONFIG.debug_process_credentials {
+                debug!(
+                    "Checking: timed out checking footer {}",
+                    self.footer_index.get()
+                );
+            }
+            self.policy.map(|policy| policy.cancel());
+            self.done(Err(ProcessCheckError::InternalError));
+        }
+    }
+}
+
 impl AppCredentialsPolicyClient<'static> for ProcessCheckerMachine {
     fn check_done(
         &self,
         result: Result<CheckResult, ErrorCode>,
         _credentials: TbfFooterV2Credentials,
-        _integrity_region: &'static [u8],
+        integrity_region: &'static [u8],
     ) {
         if config::CONFIG.debug_process_credentials {
             debug!("Checking: check_done gave result {:?}", result);
         }
+        // Ignore results for checks that timed out, including a late result
+        // for a previous process binary.
+        let current = match self.process_binary.take() {
+            Some(pb) => {
+                let current = pb.get_integrity_region_slice().as_ptr() == integrity_region.as_ptr();
+                self.process_binary.set(pb);
+                current
+            }
+            None => false,
+        };
+        if !current || !self.checking.replace(false) {
+            return;
+        }
+        self.timeout.map(|timeout| timeout.cancel());
+
         let cont = match result {
+            Ok(result) if self.all_footers() => {
+                // Decide once all footers are checked.
+                self.record(result);
+                self.footer_index.increment();
+                true
+            }
             Ok(CheckResult::Accept) => {
-                self.client.map(|client| {
-                    if let Some(pb) = self.process_binary.take() {
-                        client.done(pb, Ok(()))
-                    }
-                });
+                self.done(Ok(()));
                 false
             }
             Ok(CheckResult::Pass) => {
@@ -433,22 +649,16 @@ impl AppCredentialsPolicyClient<'static> for ProcessCheckerMachine {
                 true
             }
             Ok(CheckResult::Reject) => {
-                self.client.map(|client| {
-                    if let Some(pb) = self.process_binary.take() {
-                        client.done(
-                            pb,
-                            Err(ProcessCheckError::CredentialsRejected(
-                                self.footer_index.get() as u32,
-                            )),
-                        )
-                    }
-                });
+                self.done(Err(ProcessCheckError::CredentialsRejected(
+                    self.footer_index.get() as u32,
+                )));
                 false
             }
             Err(e) => {
                 if config::CONFIG.debug_process_credentials {
                     debug!("Checking: error checking footer {:?}", e);
                 }
+                self.record(CheckResult::Pass);
                 self.footer_index.increment();
                 true
             }
@@ -461,3 +671,233 @@ impl AppCredentialsPolicyClient<'static> for ProcessCheckerMachine {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::process_binary::test::{tbf_object, PROGRAM_WITH_FOOTERS};
+    use crate::process_binary::KernelVersionPolicy;
+    use std::boxed::Box;
+    use std::string::ToString;
+    use tock_tbf::types::TbfFooterV2CredentialsType;
+
+    /// App with a SHA256 credentials footer.
+    static APP: [u8; 0x100] = {
+        let mut flash = tbf_object(1, &PROGRAM_WITH_FOOTERS);
+        flash[0x40] = 128;
+        flash[0x42] = 36;
+        flash[0x44] = 3;
+        flash
+    };
+
+    /// App with a SHA256 credentials footer followed by a SHA384 one.
+    static TWO_FOOTER_APP: [u8; 0x100] = {
+        let mut flash = tbf_object(1, &PROGRAM_WITH_FOOTERS);
+        flash[0x40] = 128;
+        flash[0x42] = 36;
+        flash[0x44] = 3;
+        flash[0x68] = 128;
+        flash[0x6a] = 52;
+        flash[0x6c] = 4;
+        flash
+    };
+
+    /// Policy that accepts SHA256 credentials and rejects all others. In
+    /// `FooterCheckMode::AllFooters` it requires every footer to be accepted.
+    struct ShaPolicy {
+        mode: FooterCheckMode,
+        client: OptionalCell<&'static dyn AppCredentialsPolicyClient<'static>>,
+    }
+
+    impl AppCredentialsPolicy<'static> for ShaPolicy {
+        fn require_credentials(&self) -> bool {
+            true
+        }
+
+        fn check_credentials(
+            &self,
+            credentials: TbfFooterV2Credentials,
+            integrity_region: &'static [u8],
+        ) -> Result<(), (ErrorCode, TbfFooterV2Credentials, &'static [u8])> {
+            let result = match credentials.format() {
+                TbfFooterV2CredentialsType::SHA256 => CheckResult::Accept,
+                _ => CheckResult::Reject,
+            };
+            self.client
+                .map(|client| client.check_done(Ok(result), credentials, integrity_region));
+            Ok(())
+        }
+
+        fn set_client(&self, client: &'static dyn AppCredentialsPolicyClient<'static>) {
+            self.client.set(client);
+        }
+
+        fn footer_check_mode(&self) -> FooterCheckMode {
+            self.mode
+        }
+
+        fn decide(&self, results: &[CheckResult]) -> CheckResult {
+            if !results.is_empty() && results.iter().all(|r| *r == CheckResult::Accept) {
+                CheckResult::Accept
+            } else {
+                CheckResult::Reject
+            }
+        }
+    }
+
+    /// Policy that starts every check but never finishes one.
+    struct HungPolicy {
+        cancelled: Cell<bool>,
+    }
+
+    impl AppCredentialsPolicy<'static> for HungPolicy {
+        fn require_credentials(&self) -> bool {
+            true
+        }
+
+        fn check_credentials(
+            &self,
+            _credentials: TbfFooterV2Credentials,
+            _integrity_region: &'static [u8],
+        ) -> Result<(), (ErrorCode, TbfFooterV2Credentials, &'static [u8])> {
+            Ok(())
+        }
+
+        fn set_client(&self, _client: &'static dyn AppCredentialsPolicyClient<'static>) {}
+
+        fn cancel(&self) {
+            self.cancelled.set(true);
+        }
+    }
+
+    struct MockTimeout {
+        running: Cell<bool>,
+    }
+
+    impl CheckTimeout for MockTimeout {
+        fn set_client(&self, _client: &'static dyn CheckTimeoutClient) {}
+
+        fn start(&self) {
+            self.running.set(true);
+        }
+
+        fn cancel(&self) {
+            self.running.set(false);
+        }
+    }
+
+    struct Client {
+        results: Cell<usize>,
+        internal_errors: Cell<usize>,
+        rejected: Cell<Option<u32>>,
+        accepted: Cell<bool>,
+    }
+
+    impl ProcessCheckerMachineClient for Client {
+        fn done(&self, _process_binary: ProcessBinary, result: Result<(), ProcessCheckError>) {
+            self.results.set(self.results.get() + 1);
+            match result {
+                Ok(()) => self.accepted.set(true),
+                Err(ProcessCheckError::InternalError) => {
+                    self.internal_errors.set(self.internal_errors.get() + 1);
+                }
+                Err(ProcessCheckError::CredentialsRejected(index)) => {
+                    self.rejected.set(Some(index));
+                }
+                Err(ProcessCheckError::CredentialsNotAccepted) => {}
+            }
+        }
+    }
+
+    fn client() -> &'static Client {
+        Box::leak(Box::new(Client {
+            results: Cell::new(0),
+            internal_errors: Cell::new(0),
+            rejected: Cell::new(None),
+            accepted: Cell::new(false),
+        }))
+    }
+
+    fn check_two_footers(mode: FooterCheckMode) -> &'static Client {
+        let policy: &'static ShaPolicy = Box::leak(Box::new(ShaPolicy {
+            mode,
+            client: OptionalCell::empty(),
+        }));
+        let machine: &'static ProcessCheckerMachine =
+            Box::leak(Box::new(ProcessCheckerMachine::new(policy)));
+        let client = client();
+        policy.set_client(machine);
+        machine.set_client(client);
+
+        let pb = ProcessBinary::create(
+            &TWO_FOOTER_APP,
+            40,
+            2,
+            KernelVersionPolicy::Ignore,
+            usize::MAX,
+        )
+        .unwrap();
+        assert!(machine.check(pb).is_ok());
+        assert_eq!(client.results.get(), 1);
+        client
+    }
+
+    #[test]
+    fn display_names_rejected_credential() {
+        assert_eq!(
+            ProcessCheckError::CredentialsRejected(2).to_string(),
+            "credential #2 rejected by verifier"
+        );
+        assert_eq!(
+            ProcessCheckError::CredentialsNotAccepted.to_string(),
+            "no acceptable credentials found"
+        );
+    }
+
+    #[test]
+    fn hung_verifier_times_out() {
+        let policy: &'static HungPolicy = Box::leak(Box::new(HungPolicy {
+            cancelled: Cell::new(false),
+        }));
+        let machine: &'static ProcessCheckerMachine =
+            Box::leak(Box::new(ProcessCheckerMachine::new(policy)));
+        let timeout: &'static MockTimeout = Box::leak(Box::new(MockTimeout {
+            running: Cell::new(false),
+        }));
+        let client = client();
+        machine.set_client(client);
+        machine.set_timeout(timeout);
+
+        let pb =
+            ProcessBinary::create(&APP, 40, 2, KernelVersionPolicy::Ignore, usize::MAX).unwrap();
+        let integrity_region = pb.get_integrity_region_slice();
+        let (credentials, _) = tock_tbf::parse::parse_tbf_footer(pb.footers).unwrap();
+        assert!(machine.check(pb).is_ok());
+        assert!(timeout.running.get());
+        assert_eq!(client.results.get(), 0);
+
+        machine.timeout_expired();
+        assert_eq!(client.results.get(), 1);
+        assert_eq!(client.internal_errors.get(), 1);
+        assert!(policy.cancelled.get());
+
+        // A late result from the verifier is ignored.
+        machine.check_done(Ok(CheckResult::Accept), credentials, integrity_region);
+        assert_eq!(client.results.get(), 1);
+    }
+
+    #[test]
+    fn first_accept_stops_at_accepted_footer() {
+        let client = check_two_footers(FooterCheckMode::FirstAccept);
+        assert!(client.accepted.get());
+    }
+
+    #[test]
+    fn all_footers_checks_every_footer() {
+        let client = check_two_footers(FooterCheckMode::AllFooters);
+        assert!(!client.accepted.get());
+        assert_eq!(client.rejected.get(), Some(1));
+    }
+}
@@ -261,7 +261,12 @@ fn load_processes_from_flash<C: Chip>(
                     | ProcessBinaryError::IncompatibleKernelVersion { .. }
                     | ProcessBinaryError::IncorrectFlashAddress { .. }
                     | ProcessBinaryError::NotEnabledProcess
-                    | ProcessBinaryError::Padding => {
+                    | ProcessBinaryError::Padding
+                    | ProcessBinaryError::BinaryEndInsideHeader { .. }
+                    | ProcessBinaryError::FooterRegionTooLarge { .. } => {
+                        if config::CONFIG.debug_load_processes {
+                            debug!("Skipping process binary: {:?}.", err);
+                        }
                         // Skip this binary and move to the next one.
                         continue;
                     }
@@ -334,8 +339,14 @@ fn discover_process_binary(
         .get(app_flash.len()..)
         .ok_or((flash, ProcessBinaryError::NotEnoughFlash))?;
 
-    let pb = ProcessBinary::create(app_flash, header_length as usize, version, true)
-        .map_err(|e| (remaining_flash, e))?;
+    let pb = ProcessBinary::create(
+        app_flash,
+        header_length as usize,
+        version,
+        config::CONFIG.kernel_version_policy,
+        config::CONFIG.max_footer_size,
+    )
+    .map_err(|e| (remaining_flash, e))?;
 
     Ok((remaining_flash, pb))
 }
@@ -561,9 +572,14 @@ impl<'a, C: Chip> SequentialProcessLoaderMachine<'a, C> {
                     .set(SequentialProcessLoaderMachineState::LoadProcesses);
                 self.deferred_call.set();
             }
+            Err(ProcessBinaryError::Padding) => {
+                // Padding between apps is not a process, so there is nothing
+                // to report. Move on to the next item in flash.
+                self.deferred_call.set();
+            }
             Err(e) => {
                 if config::CONFIG.debug_load_processes {
-                    debug!("Loading: unable to create ProcessBinary");
+                    debug!("Loading: unable to create ProcessBinary: {:?}", e);
                 }
 
                 // Other process binary errors indicate the process is not
@@ -635,7 +651,13 @@ impl<'a, C: Chip> SequentialProcessLoaderMachine<'a, C> {
             .ok_or(ProcessBinaryError::NotEnoughFlash)?;
         self.flash.set(remaining_flash);
 
-        let pb = ProcessBinary::create(app_flash, header_length as usize, version, true)?;
+        let pb = ProcessBinary::create(
+            app_flash,
+            header_length as usize,
+            version,
+            config::CONFIG.kernel_version_policy,
+            config::CONFIG.max_footer_size,
+        )?;
 
         Ok(pb)
     }
@@ -933,7 +955,7 @@ impl<'a, C: Chip> crate::process_checker::ProcessCheckerMachineClient
             Err(e) => {
                 if config::CONFIG.debug_load_processes {
                     debug!(
-                        "Loading: Process {} check failed {:?}",
+                        "Loading: Process {} check failed: {}",
                         process_binary.header.get_package_name().unwrap_or(""),
                         e
                     );